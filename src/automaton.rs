@@ -0,0 +1,435 @@
+// A cellular automaton engine: a grid of cells, a rule for how they change, which cells count
+// as neighbours, and what happens at the edges of the grid.
+
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+
+
+/// Which cells are neighbours, as (down, across) offsets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Neighbourhood {
+    Moore,                      // the 8 cells around
+    VonNeumann,                 // above, right, below and left
+    Offsets(Vec<(i32, i32)>),
+}
+
+
+impl Neighbourhood {
+
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        match self {
+            Neighbourhood::Moore => vec![(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
+            Neighbourhood::VonNeumann => vec![(-1, 0), (0, 1), (1, 0), (0, -1)],
+            Neighbourhood::Offsets(offsets) => offsets.clone(),
+        }
+    }
+
+    // how far away the furthest neighbour is.
+    fn radius(&self) -> usize {
+        self.offsets().iter()
+            .map(|&(d, a)| d.unsigned_abs().max(a.unsigned_abs()) as usize)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+
+impl FromStr for Neighbourhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "moore" => Ok(Neighbourhood::Moore),
+            "von-neumann" => Ok(Neighbourhood::VonNeumann),
+            _ => Err(format!("'{}' isn't a neighbourhood: use moore or von-neumann", s)),
+        }
+    }
+}
+
+
+/// What's beyond the edge of the grid: nothing (Clipped), the other side of the grid (Toroidal),
+/// or an endless background of one cell value that the grid grows into (Infinite).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    Clipped,
+    Toroidal,
+    Infinite,
+}
+
+
+impl FromStr for Boundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clipped" => Ok(Boundary::Clipped),
+            "toroidal" => Ok(Boundary::Toroidal),
+            "infinite" => Ok(Boundary::Infinite),
+            _ => Err(format!("'{}' isn't a boundary: use clipped, toroidal or infinite", s)),
+        }
+    }
+}
+
+
+/// How firing cells cascade after the phases of a step.  With OncePerStep a cell fires at most
+/// once a step and ignores its neighbours after that (the octopuses); with Repeated a cell fires
+/// every time it's over the top, so the rule must settle down eventually (e.g. a sandpile).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cascade {
+    Off,
+    OncePerStep,
+    Repeated,
+}
+
+
+/// The rule for an automaton.  A step is one or more phases, each of which updates every cell
+/// at once from the cells as they were before the phase, followed by an optional cascade.
+pub trait Rule {
+    type Cell: Clone + Eq + Hash;
+
+    /// the neighbourhood for each phase of a step.
+    fn phases(&self) -> Vec<Neighbourhood>;
+
+    /// the new value of a cell in a phase.  The neighbours are in the order of the
+    /// neighbourhood's offsets; None if they're off the edge of a clipped grid.
+    fn next(&self, phase: usize, cell: &Self::Cell, neighbours: &[Option<&Self::Cell>]) -> Self::Cell;
+
+    fn cascade(&self) -> Cascade {
+        Cascade::Off
+    }
+
+    /// the cells that a firing cell passes something on to.
+    fn cascade_neighbourhood(&self) -> Neighbourhood {
+        Neighbourhood::Moore
+    }
+
+    fn fires(&self, _cell: &Self::Cell) -> bool {
+        false
+    }
+
+    /// what a cell becomes when it fires; with OncePerStep this happens at the end of the step.
+    fn fired(&self, cell: &Self::Cell) -> Self::Cell {
+        cell.clone()
+    }
+
+    /// what a cell becomes when a neighbour fires.
+    fn receive(&self, cell: &Self::Cell) -> Self::Cell {
+        cell.clone()
+    }
+}
+
+
+/// The cells, row by row.  'background' is the value of every cell off the grid when the
+/// boundary is Infinite.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<C> {
+    pub height: usize,
+    pub width: usize,
+    pub cells: Vec<C>,
+    pub background: C,
+}
+
+
+impl<C: fmt::Display> fmt::Display for Grid<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.cells.chunks(self.width.max(1)) {
+            for cell in row.iter() {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl<C: Clone> Grid<C> {
+
+    pub fn new(rows: Vec<Vec<C>>, background: C) -> Result<Self, String> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |r| r.len());
+        if width == 0 {
+            return Err("The grid is empty".to_string());
+        }
+        if let Some(i) = rows.iter().position(|r| r.len() != width) {
+            return Err(format!("Row {} isn't {} cells wide", i + 1, width));
+        }
+        Ok(Self {height, width, cells: rows.into_iter().flatten().collect(), background})
+    }
+
+    // the grid with 'by' cells of background added all the way round.
+    fn grown(&self, by: usize) -> Self {
+        let width = self.width + 2 * by;
+        let height = self.height + 2 * by;
+        let mut cells = vec![self.background.clone(); width * height];
+        for (down, row) in self.cells.chunks(self.width).enumerate() {
+            let start = (down + by) * width + by;
+            cells[start..start + self.width].clone_from_slice(row);
+        }
+        Self {height, width, cells, background: self.background.clone()}
+    }
+}
+
+
+/// What happened in a step: how many cells ended up different, and how many times cells fired.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepStats {
+    pub changed: usize,
+    pub fired: usize,
+}
+
+
+/// The stats for each step until the grid first repeated itself (or a limit was hit).  As the
+/// automaton is deterministic it then cycles: 'cycle' is (transient, period), where transient
+/// is the number of steps before the cycle starts and period is its length.
+#[derive(Clone, Debug)]
+pub struct History {
+    pub stats: Vec<StepStats>,
+    pub cycle: Option<(usize, usize)>,
+}
+
+
+impl History {
+
+    /// the sum of a stat over the first 'n' steps; None if that's past the end with no cycle.
+    pub fn total<F>(&self, n: u64, stat: F) -> Option<u128>
+        where F: Fn(&StepStats) -> usize
+    {
+        let sum = |range: std::ops::Range<usize>| self.stats[range].iter().map(|s| stat(s) as u128).sum::<u128>();
+        if n <= self.stats.len() as u64 {
+            return Some(sum(0..n as usize));
+        }
+        let (transient, period) = self.cycle?;
+        let cycles = (n - transient as u64) / period as u64;
+        let rest = ((n - transient as u64) % period as u64) as usize;
+        Some(sum(0..transient) + cycles as u128 * sum(transient..transient + period) + sum(transient..transient + rest))
+    }
+}
+
+
+/// How a run to a cycle ended: 'done' said to stop at a generation, the grid went round a
+/// cycle of (transient, period) steps without it doing so, or the limit was hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ending {
+    Done(usize),
+    Cycle(usize, usize),
+    Limit,
+}
+
+
+pub struct Automaton<R: Rule> {
+    pub rule: R,
+    pub grid: Grid<R::Cell>,
+    pub boundary: Boundary,
+    pub generation: usize,
+}
+
+
+impl<R: Rule> Automaton<R> {
+
+    pub fn new(rule: R, grid: Grid<R::Cell>, boundary: Boundary) -> Result<Self, String> {
+        if boundary == Boundary::Infinite && rule.cascade() != Cascade::Off {
+            return Err("A cascade can't run on an infinite grid".to_string());
+        }
+        Ok(Self {rule, grid, boundary, generation: 0})
+    }
+
+    // the index of the cell at an offset from 'at'; None if it's off the grid.
+    fn offset(&self, at: usize, (dd, da): (i32, i32)) -> Option<usize> {
+        let (height, width) = (self.grid.height as i64, self.grid.width as i64);
+        let mut down = (at / self.grid.width) as i64 + dd as i64;
+        let mut across = (at % self.grid.width) as i64 + da as i64;
+        if self.boundary == Boundary::Toroidal {
+            down = down.rem_euclid(height);
+            across = across.rem_euclid(width);
+        }
+        if (0..height).contains(&down) && (0..width).contains(&across) {
+            Some((down * width + across) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn neighbour(&self, at: usize, offset: (i32, i32)) -> Option<&R::Cell> {
+        match (self.offset(at, offset), self.boundary) {
+            (Some(i), _) => Some(&self.grid.cells[i]),
+            (None, Boundary::Infinite) => Some(&self.grid.background),
+            (None, _) => None,
+        }
+    }
+
+    fn phase(&mut self, phase: usize, neighbourhood: &Neighbourhood) {
+        if self.boundary == Boundary::Infinite {
+            self.grid = self.grid.grown(neighbourhood.radius());
+        }
+        let offsets = neighbourhood.offsets();
+        let mut neighbours: Vec<Option<&R::Cell>> = Vec::with_capacity(offsets.len());
+        let mut cells: Vec<R::Cell> = Vec::with_capacity(self.grid.cells.len());
+        for (at, cell) in self.grid.cells.iter().enumerate() {
+            neighbours.clear();
+            neighbours.extend(offsets.iter().map(|&o| self.neighbour(at, o)));
+            cells.push(self.rule.next(phase, cell, &neighbours));
+        }
+        let background = if self.boundary == Boundary::Infinite {
+            let around = vec![Some(&self.grid.background); offsets.len()];
+            self.rule.next(phase, &self.grid.background, &around)
+        } else {
+            self.grid.background.clone()
+        };
+        self.grid.cells = cells;
+        self.grid.background = background;
+    }
+
+    // fire cells until none are left to fire; returns how many times cells fired.
+    fn cascade(&mut self) -> usize {
+        let cascade = self.rule.cascade();
+        if cascade == Cascade::Off {
+            return 0;
+        }
+        let offsets = self.rule.cascade_neighbourhood().offsets();
+        let mut done = vec![false; self.grid.cells.len()];
+        let mut queue: Vec<usize> = Vec::new();
+        for (at, cell) in self.grid.cells.iter().enumerate() {
+            if self.rule.fires(cell) {
+                done[at] = cascade == Cascade::OncePerStep;
+                queue.push(at);
+            }
+        }
+        let mut fired: usize = 0;
+        while let Some(at) = queue.pop() {
+            if cascade == Cascade::Repeated {
+                if !self.rule.fires(&self.grid.cells[at]) {
+                    continue;
+                }
+                self.grid.cells[at] = self.rule.fired(&self.grid.cells[at]);
+            }
+            fired += 1;
+            for &o in offsets.iter() {
+                let n = match self.offset(at, o) {
+                    Some(n) if !done[n] => n,
+                    _ => continue,
+                };
+                self.grid.cells[n] = self.rule.receive(&self.grid.cells[n]);
+                if self.rule.fires(&self.grid.cells[n]) {
+                    done[n] = cascade == Cascade::OncePerStep;
+                    queue.push(n);
+                }
+            }
+            if cascade == Cascade::Repeated && self.rule.fires(&self.grid.cells[at]) {
+                queue.push(at);
+            }
+        }
+        if cascade == Cascade::OncePerStep {
+            for (at, &d) in done.iter().enumerate() {
+                if d {
+                    self.grid.cells[at] = self.rule.fired(&self.grid.cells[at]);
+                }
+            }
+        }
+        fired
+    }
+
+    /// Run one step: all the phases and then the cascade.
+    pub fn step(&mut self) -> StepStats {
+        let before = self.grid.clone();
+        for (phase, neighbourhood) in self.rule.phases().iter().enumerate() {
+            self.phase(phase, neighbourhood);
+        }
+        let fired = self.cascade();
+        self.generation += 1;
+        let changed = if self.grid.width == before.width {
+            self.grid.cells.iter().zip(before.cells.iter()).filter(|(a, b)| a != b).count()
+        } else {
+            // an infinite grid has grown, so compare with the old cells where they are now.
+            let by = (self.grid.width - before.width) / 2;
+            let old = before.grown(by);
+            self.grid.cells.iter().zip(old.cells.iter()).filter(|(a, b)| a != b).count()
+        };
+        StepStats {changed, fired}
+    }
+
+    /// Step until 'done' says so after a step, giving up after 'limit' steps if there is one.
+    /// Returns the generation it stopped at, or None if it hit the limit.
+    pub fn run_until<P>(&mut self, limit: Option<usize>, mut done: P) -> Option<usize>
+        where P: FnMut(&Self, &StepStats) -> bool
+    {
+        let mut steps: usize = 0;
+        while limit.is_none_or(|l| steps < l) {
+            let stats = self.step();
+            steps += 1;
+            if done(self, &stats) {
+                return Some(self.generation);
+            }
+        }
+        None
+    }
+
+    // step another grid, leaving this one (and the generation) as it was.
+    fn advance(&mut self, grid: &mut Grid<R::Cell>) {
+        let generation = self.generation;
+        std::mem::swap(&mut self.grid, grid);
+        self.step();
+        std::mem::swap(&mut self.grid, grid);
+        self.generation = generation;
+    }
+
+    /// Step until 'done' says so after a step, or until the grid repeats, giving up after 'limit'
+    /// steps.  As the automaton is deterministic, once it repeats without 'done' it never will.
+    /// The repeat is found with Brent's algorithm, so only a few grids are kept however long it
+    /// runs: the grid is saved at each power of two steps and compared with every step after,
+    /// which finds the period; then two grids 'period' steps apart are stepped together from the
+    /// start until they meet, which finds the transient (counted from the starting generation).
+    pub fn run_to_cycle<P>(&mut self, limit: usize, mut done: P) -> Ending
+        where P: FnMut(&Self, &StepStats) -> bool
+    {
+        let start = self.grid.clone();
+        let mut saved = self.grid.clone();
+        let (mut power, mut period) = (1, 0);
+        for _ in 0..limit {
+            let stats = self.step();
+            period += 1;
+            if done(self, &stats) {
+                return Ending::Done(self.generation);
+            }
+            if self.grid == saved {
+                let mut behind = start.clone();
+                let mut ahead = start;
+                for _ in 0..period {
+                    self.advance(&mut ahead);
+                }
+                let mut transient = 0;
+                while behind != ahead {
+                    self.advance(&mut behind);
+                    self.advance(&mut ahead);
+                    transient += 1;
+                }
+                return Ending::Cycle(transient, period);
+            }
+            if period == power {
+                saved = self.grid.clone();
+                power *= 2;
+                period = 0;
+            }
+        }
+        Ending::Limit
+    }
+
+    /// Step until a grid repeats one seen before, or for at most 'limit' steps.  With an infinite
+    /// boundary the grid keeps growing, so it'll only stop at the limit.
+    pub fn history(&mut self, limit: usize) -> History {
+        let mut seen: HashMap<Grid<R::Cell>, usize> = HashMap::new();
+        let mut stats: Vec<StepStats> = Vec::new();
+        seen.insert(self.grid.clone(), 0);
+        while stats.len() < limit {
+            stats.push(self.step());
+            if let Some(&first) = seen.get(&self.grid) {
+                return History {cycle: Some((first, stats.len() - first)), stats};
+            }
+            seen.insert(self.grid.clone(), stats.len());
+        }
+        History {stats, cycle: None}
+    }
+}
@@ -5,92 +5,134 @@
 //flying into the ocean!
 // etc.
 
-use std::num::ParseIntError;
+use std::collections::VecDeque;
 
 use crate::utils;
 
 const NUMBERS: [u32; 10] = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SPARKLINE_WIDTH: usize = 60;
 
 
-fn count_depth_increases(numbers: &[u32]) -> Result<u32, String> {
-    let mut n1 = numbers.get(0).expect("No numbers passed");
+/// Count how many times the sum of a sliding window of k depths increases.  Two neighbouring
+/// windows share all but one depth, so that's just comparing each depth with the one k before it.
+fn count_window_increases<I>(depths: I, k: usize) -> Result<u32, String>
+    where I: IntoIterator<Item=u32>
+{
+    if k == 0 {
+        return Err("The window must be at least 1 depth".to_string());
+    }
+    let mut window: VecDeque<u32> = VecDeque::with_capacity(k + 1);
     let mut count: u32 = 0;
-    for n2 in numbers[1..].iter() {
-        if n2 > n1 {
+    let mut seen: usize = 0;
+    for depth in depths {
+        seen += 1;
+        window.push_back(depth);
+        if window.len() > k && depth > window.pop_front().unwrap() {
             count += 1;
         }
-        n1 = n2;
+    }
+    if seen <= k {
+        return Err(format!("Not enough numbers for a window of {}: {}", k, seen));
     }
     Ok(count)
 }
 
 
-fn count_depth_increases3(numbers: &[u32]) -> Result<u32, String> {
-    if numbers.len() < 4 {
-        return Err("Not enough numbers".to_string());
-    }
-    let mut count: u32 = 0;
-    let mut sum: u32 = 0;
-    let mut ns: [u32; 3] = [0; 3];
-    for (i, n) in numbers.iter().enumerate() {
-        if i < 3 {
-            ns[i] = *n;
-            sum += n;
-            continue;
-        }
-        let next_sum = sum - ns[i % 3] + n;
-        if next_sum > sum {
-            count += 1;
+// parse the depths, failing with the (1 based) line number of the first bad one.
+fn parse_depths<S>(lines: &[S]) -> Result<Vec<u32>, String>
+    where S: AsRef<str>
+{
+    lines.iter()
+        .enumerate()
+        .map(|(i, line)| line.as_ref().trim().parse::<u32>()
+            .map_err(|e| format!("Line {}: '{}' isn't a depth: {}", i + 1, line.as_ref(), e)))
+        .collect()
+}
+
+
+#[derive(Clone, Debug)]
+struct DepthProfile {
+    max_depth: u32,
+    longest_run: usize,    // the most depths in a row that keep increasing
+    sparkline: String,
+}
+
+
+impl DepthProfile {
+
+    fn new(depths: &[u32]) -> Self {
+        let max_depth = depths.iter().cloned().max().unwrap_or(0);
+        let mut longest_run: usize = 0;
+        let mut run: usize = 0;
+        for (i, &depth) in depths.iter().enumerate() {
+            run = if i > 0 && depth > depths[i - 1] { run + 1 } else { 1 };
+            longest_run = longest_run.max(run);
         }
-        sum = next_sum;
-        ns[i % 3] = *n;
+        Self {max_depth, longest_run, sparkline: sparkline(depths, SPARKLINE_WIDTH)}
     }
-    Ok(count)
 }
 
 
-
-fn extract_numbers(parsed: &[Result<u32, ParseIntError>]) -> Vec<u32> {
-    parsed
-        .iter()
-        .map(|x| {
-            match *x {
-                Ok(v) => v,
-                Err(_) => 0 as u32,
-            }
+// downsample the depths into 'width' buckets (by their mean) and draw a bar for each one.
+fn sparkline(depths: &[u32], width: usize) -> String {
+    if depths.is_empty() {
+        return String::new();
+    }
+    let buckets = width.min(depths.len());
+    let means = (0..buckets)
+        .map(|b| {
+            let bucket = &depths[b * depths.len() / buckets..(b + 1) * depths.len() / buckets];
+            bucket.iter().map(|&d| d as u64).sum::<u64>() / bucket.len() as u64
         })
+        .collect::<Vec<_>>();
+    let lo = *means.iter().min().unwrap();
+    let hi = *means.iter().max().unwrap();
+    let top = (SPARKS.len() - 1) as u64;
+    means.iter()
+        .map(|&m| if hi == lo { SPARKS[0] } else { SPARKS[((m - lo) * top / (hi - lo)) as usize] })
         .collect()
 }
 
-pub fn day1_1() {
-    println!("First let's just do the test with the depths:");
-    match count_depth_increases(&NUMBERS) {
-        Ok(n) => println!("The number of increases is {}", n),
-        Err(s) => println!("{0}", s),
-    }
 
-    println!("Now let's read the depths file and then find the number of increases:");
-    let parsed_numbers = utils::read_file::<u32>("./input/day01-1.txt");
-    let numbers = extract_numbers(&parsed_numbers);
-    match count_depth_increases(&numbers) {
-        Ok(n) => println!("The number of increases is {}", n),
+fn report(depths: &[u32], k: usize) {
+    match count_window_increases(depths.iter().cloned(), k) {
+        Ok(n) => println!("The number of increases with a window of {} is {}", k, n),
         Err(s) => println!("{0}", s),
     }
 }
 
 
-pub fn day1_2() {
+fn run(options: &[String], default_window: usize) {
+    let k = utils::option_value::<usize>(options, "--window")
+        .expect("Bad --window option")
+        .unwrap_or(default_window);
     println!("First let's just do the test with the depths:");
-    match count_depth_increases3(&NUMBERS) {
-        Ok(n) => println!("The number of increases is {}", n),
-        Err(s) => println!("{0}", s),
-    }
+    report(&NUMBERS, k);
 
     println!("Now let's read the depths file and then find the number of increases:");
-    let parsed_numbers = utils::read_file::<u32>("./input/day01-1.txt");
-    let numbers = extract_numbers(&parsed_numbers);
-    match count_depth_increases3(&numbers) {
-        Ok(n) => println!("The number of increases is {}", n),
-        Err(s) => println!("{0}", s),
-    }
+    let file_name = utils::input_file(options, "./input/day01-1.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Couldn't read file");
+    let depths = match parse_depths(&lines) {
+        Ok(depths) => depths,
+        Err(s) => {
+            println!("{}", s);
+            return;
+        },
+    };
+    report(&depths, k);
+
+    let profile = DepthProfile::new(&depths);
+    println!("Max depth: {}, longest increasing run: {} depths", profile.max_depth, profile.longest_run);
+    println!("Profile: {}", profile.sparkline);
+}
+
+
+pub fn day1_1(options: &[String]) {
+    run(options, 1);
+}
+
+
+pub fn day1_2(options: &[String]) {
+    run(options, 3);
 }
@@ -12,6 +12,7 @@
 //opposite result of what you might expect.
 
 use std::str::FromStr;
+use std::collections::HashMap;
 
 use crate::utils;
 
@@ -24,6 +25,10 @@ pub enum CommandError {
     DecodeError(String),
     #[error("Invalid command")]
     InvalidError(String),
+    #[error("Arithmetic overflowed")]
+    Overflow(String),
+    #[error("Script error at line {0}, column {1}: {2}")]
+    ScriptError(usize, usize, String),
 }
 
 
@@ -32,6 +37,34 @@ pub enum Direction {
     Forward,
     Down,
     Up,
+    Back,
+    Surface,
+}
+
+
+impl FromStr for Direction {
+    type Err = CommandError;
+
+    fn from_str(verb: &str) -> Result<Self, Self::Err> {
+        match verb {
+            "forward" => Ok(Direction::Forward),
+            "down" => Ok(Direction::Down),
+            "up" => Ok(Direction::Up),
+            "back" => Ok(Direction::Back),
+            "surface" => Ok(Direction::Surface),
+            _ => Err(CommandError::InvalidError(
+                format!("Command is not one of 'forward', 'down', 'up', 'back' or 'surface': {}", verb))),
+        }
+    }
+}
+
+
+impl Direction {
+
+    // surface is the only command that doesn't take a value.
+    fn takes_value(&self) -> bool {
+        !matches!(self, Direction::Surface)
+    }
 }
 
 
@@ -46,99 +79,351 @@ impl FromStr for Command {
     type Err = CommandError;
 
     fn from_str(cmd: &str) -> Result<Self, Self::Err> {
-        let parts = cmd.split(" ").collect::<Vec<_>>();
-        if parts.len() != 2 {
-            return Err(CommandError::DecodeError(format!("Missing space in passed string: {}", cmd)));
+        let parts = cmd.split(' ').collect::<Vec<_>>();
+        let direction = parts[0].parse::<Direction>()?;
+        match (direction.takes_value(), parts.len()) {
+            (false, 1) => Ok(Self {direction, value: 0}),
+            (true, 2) => {
+                let value = parts[1].parse::<u32>().map_err(|s| CommandError::InvalidError(format!("Command value didn't decode to u32: {} - {}", parts[1], s)))?;
+                Ok(Self {direction, value})
+            },
+            _ => Err(CommandError::DecodeError(format!("Wrong number of parts in passed string: {}", cmd))),
         }
-        let command = match parts[0] {
-            "forward" => Ok(Direction::Forward),
-            "down" => Ok(Direction::Down),
-            "up" => Ok(Direction::Up),
-            _ => Err(CommandError::InvalidError(format!("Command is not one of 'forward', 'down', or 'up'"))),
-        }?;
-        let value = parts[1].parse::<u32>().map_err(|s| CommandError::InvalidError(format!("Command value didn't decode to u32: {} - {}", parts[1], s)))?;
-        Ok(Self {direction: command, value})
     }
 }
 
 
-fn determine_value(commands: &[Command]) -> u32 {
-    let mut f: u32 = 0;
-    let mut d: u32 = 0;
-    for command in commands.iter() {
-        match command.direction {
-            Direction::Forward => { f += command.value; },
-            Direction::Up => { d -= command.value; },
-            Direction::Down => { d += command.value; },
+// --- The command language used to script longer manoeuvres.  On top of the plain commands (one
+// per line) it has:
+//
+//   # comments to the end of the line
+//   macro <name> { ... }     define a named sequence of commands, only at the top level
+//   <name>                   use a macro defined earlier
+//   repeat <n> { ... }       repeat the commands n times
+//
+// A command and its value, or 'macro'/'repeat' up to the '{', must be on one line; the commands
+// in a block can go over as many lines as they like.  A script is expanded into a flat Program
+// of commands, each with the line it came from.
+
+// stop scripts that expand to something silly, e.g. nested repeats of a million.  Each time
+// round a repeat counts as at least one command, even if its body is empty.
+const MAX_COMMANDS: usize = 10_000_000;
+
+type Program = Vec<(usize, Command)>;
+
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+
+// split the script into tokens; '{' and '}' are always tokens by themselves.
+fn tokenize(script: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    for (l, line) in script.lines().enumerate() {
+        let mut current: Option<Token> = None;
+        for (c, ch) in line.chars().enumerate() {
+            if ch == '#' {
+                break;
+            }
+            if ch.is_whitespace() || ch == '{' || ch == '}' {
+                tokens.extend(current.take());
+                if ch != '{' && ch != '}' {
+                    continue;
+                }
+            }
+            let token = current.get_or_insert_with(|| Token {text: String::new(), line: l + 1, column: c + 1});
+            token.text.push(ch);
+            if ch == '{' || ch == '}' {
+                tokens.extend(current.take());
+            }
+        }
+        tokens.extend(current.take());
+    }
+    tokens
+}
+
+
+struct ScriptParser {
+    tokens: Vec<Token>,
+    pos: usize,
+    macros: HashMap<String, Program>,
+}
+
+
+impl ScriptParser {
+
+    fn error<S: AsRef<str>>(token: &Token, message: S) -> CommandError {
+        CommandError::ScriptError(token.line, token.column, message.as_ref().to_string())
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // the next token, or an error at 'after' saying what was expected instead of the end.
+    fn expect_next(&mut self, after: &Token, expected: &str) -> Result<Token, CommandError> {
+        self.next().ok_or_else(|| Self::error(after, format!("Expected {} after '{}'", expected, after.text)))
+    }
+
+    // as expect_next, but the token must be on the same line as 'after'.
+    fn expect_on_line(&mut self, after: &Token, expected: &str) -> Result<Token, CommandError> {
+        match self.tokens.get(self.pos) {
+            Some(token) if token.line == after.line => self.expect_next(after, expected),
+            _ => Err(Self::error(after, format!("Expected {} after '{}' on the same line", expected, after.text))),
+        }
+    }
+
+    fn expect_open(&mut self, after: &Token) -> Result<Token, CommandError> {
+        let token = self.expect_on_line(after, "'{'")?;
+        if token.text != "{" {
+            return Err(Self::error(&token, format!("Expected '{{' but got '{}'", token.text)));
+        }
+        Ok(token)
+    }
+
+    fn parse_value(token: &Token) -> Result<u32, CommandError> {
+        token.text.parse::<u32>()
+            .map_err(|e| Self::error(token, format!("'{}' isn't a u32: {}", token.text, e)))
+    }
+
+    fn extend(program: &mut Program, more: &[(usize, Command)], token: &Token) -> Result<(), CommandError> {
+        if program.len() + more.len() > MAX_COMMANDS {
+            return Err(Self::error(token, format!("Script expands to more than {} commands", MAX_COMMANDS)));
+        }
+        program.extend_from_slice(more);
+        Ok(())
+    }
+
+    // parse commands until the matching '}' of 'open', or the end of the script if there isn't one.
+    fn parse_block(&mut self, open: Option<&Token>) -> Result<Program, CommandError> {
+        let mut program: Program = Vec::new();
+        loop {
+            let token = match self.next() {
+                Some(token) => token,
+                None => return match open {
+                    Some(open) => Err(Self::error(open, "No matching '}' for this '{'")),
+                    None => Ok(program),
+                },
+            };
+            match token.text.as_str() {
+                "}" => {
+                    if open.is_none() {
+                        return Err(Self::error(&token, "'}' without a matching '{'"));
+                    }
+                    return Ok(program);
+                },
+                "{" => return Err(Self::error(&token, "'{' needs a 'macro' or 'repeat' before it")),
+                "macro" => {
+                    if open.is_some() {
+                        return Err(Self::error(&token, "Macros can only be defined at the top level"));
+                    }
+                    let name = self.expect_on_line(&token, "a macro name")?;
+                    if name.text.parse::<Direction>().is_ok() || ["macro", "repeat", "{", "}"].contains(&name.text.as_str()) {
+                        return Err(Self::error(&name, format!("'{}' can't be used as a macro name", name.text)));
+                    }
+                    let open = self.expect_open(&name)?;
+                    let body = self.parse_block(Some(&open))?;
+                    self.macros.insert(name.text, body);
+                },
+                "repeat" => {
+                    let count = self.expect_on_line(&token, "a repeat count")?;
+                    let n = Self::parse_value(&count)?;
+                    let open = self.expect_open(&count)?;
+                    let body = self.parse_block(Some(&open))?;
+                    let expanded = (n as usize).saturating_mul(body.len().max(1));
+                    if program.len().saturating_add(expanded) > MAX_COMMANDS {
+                        return Err(Self::error(&token, format!("Script expands to more than {} commands", MAX_COMMANDS)));
+                    }
+                    if !body.is_empty() {
+                        for _ in 0..n {
+                            Self::extend(&mut program, &body, &token)?;
+                        }
+                    }
+                },
+                verb => {
+                    if let Ok(direction) = verb.parse::<Direction>() {
+                        let value = if direction.takes_value() {
+                            Self::parse_value(&self.expect_on_line(&token, "a value")?)?
+                        } else {
+                            0
+                        };
+                        program.push((token.line, Command {direction, value}));
+                    } else if let Some(body) = self.macros.get(verb).cloned() {
+                        Self::extend(&mut program, &body, &token)?;
+                    } else {
+                        return Err(Self::error(&token, format!("Unknown command or macro '{}'", verb)));
+                    }
+                },
+            }
+        }
+    }
+}
+
+
+/// Parse a script into a flat Program.  A plain list of commands is also a valid script.
+fn parse_script(script: &str) -> Result<Program, CommandError> {
+    let mut parser = ScriptParser {tokens: tokenize(script), pos: 0, macros: HashMap::new()};
+    parser.parse_block(None)
+}
+
+
+/// How the commands steer the submarine; part 1 is Plain, part 2 uses the Aim.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Steering {
+    Plain,
+    Aim,
+}
+
+
+impl FromStr for Steering {
+    type Err = CommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Steering::Plain),
+            "aim" => Ok(Steering::Aim),
+            _ => Err(CommandError::InvalidError(format!("Steering is not one of 'plain' or 'aim': {}", s))),
+        }
+    }
+}
+
+
+// The state of the submarine; depth (and aim) can go negative if it's told to go up too far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Submarine {
+    position: i64,
+    depth: i64,
+    aim: i64,
+}
+
+
+impl Submarine {
+
+    // apply a command, returning None if any of the arithmetic overflows.
+    fn apply(&self, command: &Command, steering: Steering) -> Option<Self> {
+        let value = i64::from(command.value);
+        let mut next = *self;
+        match (steering, &command.direction) {
+            (_, Direction::Forward) => {
+                next.position = self.position.checked_add(value)?;
+                if steering == Steering::Aim {
+                    next.depth = self.depth.checked_add(self.aim.checked_mul(value)?)?;
+                }
+            },
+            (Steering::Plain, Direction::Up) => { next.depth = self.depth.checked_sub(value)? },
+            (Steering::Plain, Direction::Down) => { next.depth = self.depth.checked_add(value)? },
+            (Steering::Aim, Direction::Up) => { next.aim = self.aim.checked_sub(value)? },
+            (Steering::Aim, Direction::Down) => { next.aim = self.aim.checked_add(value)? },
+            (_, Direction::Back) => {
+                next.position = self.position.checked_sub(value)?;
+                if steering == Steering::Aim {
+                    next.depth = self.depth.checked_sub(self.aim.checked_mul(value)?)?;
+                }
+            },
+            (_, Direction::Surface) => {
+                next.depth = 0;
+                next.aim = 0;
+            },
         };
+        Some(next)
     }
-    f * d
+
+    fn value(&self) -> Option<i64> {
+        self.position.checked_mul(self.depth)
+    }
+}
+
+
+/// Pilot the submarine through the program, returning every state it was in, starting at the
+/// surface.  An overflow reports the line of the command that caused it.
+fn pilot(program: &[(usize, Command)], steering: Steering) -> Result<Vec<Submarine>, CommandError> {
+    let mut trace: Vec<Submarine> = Vec::with_capacity(program.len() + 1);
+    let mut sub = Submarine::default();
+    trace.push(sub);
+    for (line, command) in program.iter() {
+        sub = sub.apply(command, steering).ok_or_else(|| CommandError::Overflow(
+            format!("Line {}: {:?} {} overflowed at {:?}", line, command.direction, command.value, &sub)))?;
+        trace.push(sub);
+    }
+    Ok(trace)
 }
 
 
-fn determine_value_with_aim(commands: &[Command]) -> u32 {
-    let mut f: u32 = 0;
-    let mut d: u32 = 0;
-    let mut aim: u32 = 0;
-    for command in commands.iter() {
-        match command.direction {
-            Direction::Forward => { f += command.value; d += aim * command.value },
-            Direction::Up => { aim -= command.value; },
-            Direction::Down => { aim += command.value; },
+fn trace_to_csv(program: &[(usize, Command)], trace: &[Submarine]) -> String {
+    let mut csv = String::from("step,line,command,value,position,depth,aim\n");
+    for (i, sub) in trace.iter().enumerate() {
+        let (line, direction, value) = match i {
+            0 => (0, String::from("start"), 0),
+            _ => {
+                let (line, command) = &program[i - 1];
+                (*line, format!("{:?}", command.direction).to_lowercase(), command.value)
+            },
         };
+        csv.push_str(&format!("{},{},{},{},{},{},{}\n", i, line, direction, value, sub.position, sub.depth, sub.aim));
     }
-    f * d
+    csv
 }
 
 
-pub fn day2_1() {
-    println!("Day2-1, using the test data.");
-    println!("'forward 10' {:?}", "forward 10".parse::<Command>());
-    println!("'up 22' {:?}", "up 22".parse::<Command>());
-    println!("'down 1' {:?}", "down 1".parse::<Command>());
-    println!("Now get the test data");
-
-    let parsed_commands = utils::read_file::<Command>("./input/day02-test.txt");
-    //println!("{:?}", parsed_commands);
-    match parsed_commands.iter().cloned().collect::<Result<Vec<Command>, _>>() {
-        Ok(commands) => {
-            println!("{:?}", commands);
-            println!("Result value is: {:?}", determine_value(&commands));
+fn run_file(file_name: &str, steering: Steering, csv_file: Option<&String>) {
+    let script = std::fs::read_to_string(file_name).expect("file not found!");
+    let program = match parse_script(&script) {
+        Ok(program) => program,
+        Err(e) => {
+            println!("Parsing failed: {}", e);
+            return;
         },
-        Err(s) => println!("Parsing failed: {}", s),
     };
-
-    println!("Now do it with the actual day 2 data.");
-    let parsed_commands = utils::read_file::<Command>("./input/day02.txt");
-    match parsed_commands.iter().cloned().collect::<Result<Vec<Command>, _>>() {
-        Ok(commands) => {
-            println!("Result value is: {:?}", determine_value(&commands));
+    match pilot(&program, steering) {
+        Ok(trace) => {
+            let sub = trace.last().unwrap();
+            println!("Submarine ends at {:?}", sub);
+            match sub.value() {
+                Some(v) => println!("Result value is: {}", v),
+                None => println!("Result value overflowed: {} * {}", sub.position, sub.depth),
+            }
+            if let Some(csv_file) = csv_file {
+                std::fs::write(csv_file, trace_to_csv(&program, &trace))
+                    .expect("Couldn't write the csv file");
+                println!("Wrote trace to {}", csv_file);
+            }
         },
-        Err(s) => println!("Parsing failed: {}", s),
+        Err(e) => println!("Piloting failed: {:?}", e),
     };
 }
 
 
-pub fn day2_2() {
-    println!("Day2-1, using the test data.");
-    println!("Now get the test data");
-
-    let parsed_commands = utils::read_file::<Command>("./input/day02-test.txt");
-    //println!("{:?}", parsed_commands);
-    match parsed_commands.iter().cloned().collect::<Result<Vec<Command>, _>>() {
-        Ok(commands) => {
-            println!("{:?}", commands);
-            println!("Result value is: {:?}", determine_value_with_aim(&commands));
-        },
-        Err(s) => println!("Parsing failed: {}", s),
-    };
+fn run(options: &[String], default_steering: Steering) {
+    let steering = utils::option_value::<Steering>(options, "--steering")
+        .expect("Bad --steering option")
+        .unwrap_or(default_steering);
+    let csv_file = utils::option_value::<String>(options, "--csv")
+        .expect("Bad --csv option");
+    println!("Steering is {:?}.  First use the test data.", steering);
+    run_file("./input/day02-test.txt", steering, None);
 
     println!("Now do it with the actual day 2 data.");
-    let parsed_commands = utils::read_file::<Command>("./input/day02.txt");
-    match parsed_commands.iter().cloned().collect::<Result<Vec<Command>, _>>() {
-        Ok(commands) => {
-            println!("Result value is: {:?}", determine_value_with_aim(&commands));
-        },
-        Err(s) => println!("Parsing failed: {}", s),
-    };
+    let file_name = utils::input_file(options, "./input/day02.txt");
+    run_file(&file_name, steering, csv_file.as_ref());
+}
+
+
+pub fn day2_1(options: &[String]) {
+    println!("Day2-1.");
+    println!("'forward 10' {:?}", "forward 10".parse::<Command>());
+    println!("'up 22' {:?}", "up 22".parse::<Command>());
+    println!("'down 1' {:?}", "down 1".parse::<Command>());
+    run(options, Steering::Plain);
+}
+
+
+pub fn day2_2(options: &[String]) {
+    println!("Day2-2, steering with aim.");
+    run(options, Steering::Aim);
 }
@@ -11,105 +11,303 @@
 use crate::utils;
 
 
-fn aggregate_strings(nums: &[String]) -> String {
-    let size = nums[0].len();
-    let mut results: Vec<usize> = vec![0; size];
-    for num in nums.iter() {
-        for (i, c) in num.chars().enumerate() {
-            match c {
-                '1' => { results[i] += 1; },
-                _ => {},
-            };
+const MAX_WIDTH: usize = 128;
+
+
+// The diagnostic report packed into words; bit (width - 1) is the first character of a reading.
+#[derive(Clone, Debug)]
+struct Report {
+    width: usize,
+    readings: Vec<u128>,
+}
+
+
+impl Report {
+
+    fn parse<S>(lines: &[S]) -> Result<Self, String>
+        where S: AsRef<str>
+    {
+        let mut width: usize = 0;
+        let mut readings: Vec<u128> = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.as_ref().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if width == 0 {
+                width = line.len();
+                if width > MAX_WIDTH {
+                    return Err(format!("Line {}: readings can't be wider than {} bits", i + 1, MAX_WIDTH));
+                }
+            } else if line.len() != width {
+                return Err(format!("Line {}: '{}' isn't {} bits wide", i + 1, line, width));
+            }
+            let reading = u128::from_str_radix(line, 2)
+                .map_err(|e| format!("Line {}: '{}' isn't binary: {}", i + 1, line, e))?;
+            readings.push(reading);
         }
+        if readings.is_empty() {
+            return Err("No readings in the report?".to_string());
+        }
+        Ok(Self {width, readings})
     }
-    let half = nums.len() / 2;
-    results.iter().map(|&v| if v > half { '1' } else { '0' }).collect::<String>()
-}
-
-
-fn bin_to_nums(s: String) -> (u32, u32) {
-    let mut num1: u32 = 0;
-    let mut num2: u32 = 0;
-    for c in s.chars() {
-        num1 *= 2;
-        num2 *= 2;
-        match c {
-            '1' => { num1 += 1; },
-            '0' => { num2 += 1; },
-            _ => {},
-        };
-    }
-    (num1, num2)
-}
-
-fn filter_at(nums: &[String], pos: usize, most: bool) -> Vec<String> {
-    // need to count the most common bit at 'pos' and then filter by that.
-    // Note the most inverts whether it's most common => least common.
-    let mut count: usize = 0;
-    let length = nums.len();
-    let half = length / 2;
-    for num in nums.iter() {
-        match num.chars().nth(pos) {
-            Some(c) => {
-                if c == '1' { count += 1 }
-            },
-            None => {},
-        };
+
+    fn mask(&self) -> u128 {
+        if self.width == MAX_WIDTH { u128::MAX } else { (1 << self.width) - 1 }
     }
-    let mut by_bool = if half * 2 == length {
-        count >= half
-    } else {
-        count > half
-    };
-    if most == false {
-        by_bool = !by_bool;
+
+    fn to_bin(&self, v: u128) -> String {
+        format!("{:0width$b}", v, width = self.width)
     }
-    let by = if by_bool { '1' } else { '0' };
-    nums.iter()
-        .filter(|n| n.chars().nth(pos).map_or_else(|| false, |c| c == by))
-        .cloned()
-        .collect::<Vec<_>>()
-}
-
-fn filter_by(nums: &[String], most: bool) -> String {
-    // filter the list of nums by filter_at progressively moving across (by pos, starting at 0)
-    // until only one number remains.  There must be at least 1 num in nums.
-    let mut ns: Vec<String> = nums.to_vec();
-    for pos in 0..nums[0].len() {
-        ns = filter_at(&ns[..], pos, most);
-        if ns.len() == 1 {
+}
+
+
+// gamma has a bit set where the most common bit in the column is a 1; epsilon is the rest.
+fn gamma_epsilon(report: &Report) -> (u128, u128) {
+    let half = report.readings.len() / 2;
+    let counts = utils::count_column_bits(&report.readings, report.width);
+    let gamma = counts.iter()
+        .enumerate()
+        .filter(|(_, &ones)| ones > half)
+        .fold(0, |acc, (bit, _)| acc | (1 << bit));
+    (gamma, !gamma & report.mask())
+}
+
+
+// the bit criteria: keep the readings with a 1 if that's the most common bit (ties go to 1), or
+// for the least common, keep the ones with a 0 (ties go to 0).
+fn keep_ones(ones: usize, zeros: usize, most: bool) -> bool {
+    if most { ones >= zeros } else { ones < zeros }
+}
+
+
+fn filter_by(report: &Report, most: bool) -> u128 {
+    // filter the sorted readings by progressively moving across the bits from the most
+    // significant until only one number remains.  The readings left always share the bits seen
+    // so far, so they are a contiguous range that splits into 0s then 1s at the next bit; a
+    // binary search finds the split.
+    let mut sorted = report.readings.clone();
+    sorted.sort_unstable();
+    let (mut lo, mut hi) = (0, sorted.len());
+    for bit in (0..report.width).rev() {
+        if hi - lo <= 1 {
             break;
         }
+        let split = lo + sorted[lo..hi].partition_point(|&r| r & (1 << bit) == 0);
+        let (zeros, ones) = (split - lo, hi - split);
+        // if one side is empty, then keep the other side, as it's all that's left.
+        if (keep_ones(ones, zeros, most) && ones > 0) || zeros == 0 {
+            lo = split;
+        } else {
+            hi = split;
+        }
+    }
+    sorted[lo]
+}
+
+
+// --- A binary trie over the readings, to explain how the ratings are picked.
+
+// per-position counts; the tie flag matters as that's when the criteria picks 1 (most) or 0 (least).
+#[derive(Clone, Debug)]
+struct BitStats {
+    pos: usize,    // 0 is the first (most significant) character of a reading
+    ones: usize,
+    zeros: usize,
+    tie: bool,
+}
+
+
+impl BitStats {
+    fn new(pos: usize, ones: usize, zeros: usize) -> Self {
+        Self {pos, ones, zeros, tie: ones == zeros}
+    }
+}
+
+
+// the per-position stats across the whole report.
+fn bit_stats(report: &Report) -> Vec<BitStats> {
+    let total = report.readings.len();
+    utils::count_column_bits(&report.readings, report.width)
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(pos, &ones)| BitStats::new(pos, ones, total - ones))
+        .collect()
+}
+
+
+#[derive(Clone, Debug, Default)]
+struct TrieNode {
+    children: [Option<usize>; 2],
+    count: usize,             // how many readings are at or below this node
+}
+
+
+#[derive(Clone, Debug)]
+struct Trie {
+    width: usize,
+    nodes: Vec<TrieNode>,     // nodes[0] is the root
+}
+
+
+// one round of filtering: the stats of the readings that were left, which bit was kept, how
+// many readings that leaves and the first few of them.
+#[derive(Clone, Debug)]
+struct Round {
+    stats: BitStats,
+    kept: usize,
+    left: usize,
+    survivors: Vec<u128>,
+}
+
+
+impl Trie {
+
+    fn new(report: &Report) -> Self {
+        let mut nodes: Vec<TrieNode> = vec![TrieNode::default()];
+        for &reading in report.readings.iter() {
+            let mut at = 0;
+            nodes[at].count += 1;
+            for bit in (0..report.width).rev() {
+                let b = ((reading >> bit) & 1) as usize;
+                at = match nodes[at].children[b] {
+                    Some(next) => next,
+                    None => {
+                        nodes.push(TrieNode::default());
+                        let next = nodes.len() - 1;
+                        nodes[at].children[b] = Some(next);
+                        next
+                    },
+                };
+                nodes[at].count += 1;
+            }
+        }
+        Self {width: report.width, nodes}
+    }
+
+    fn count(&self, node: Option<usize>) -> usize {
+        node.map_or(0, |n| self.nodes[n].count)
+    }
+
+    // up to 'max' of the readings below a node, where 'prefix' is the bits taken to get there.
+    fn readings(&self, node: usize, prefix: u128, depth: usize, max: usize, out: &mut Vec<u128>) {
+        if depth == self.width {
+            let room = max.saturating_sub(out.len());
+            out.extend(std::iter::repeat_n(prefix, self.nodes[node].count.min(room)));
+            return;
+        }
+        for (b, child) in self.nodes[node].children.iter().enumerate() {
+            if let Some(child) = child {
+                if out.len() < max {
+                    self.readings(*child, (prefix << 1) | b as u128, depth + 1, max, out);
+                }
+            }
+        }
+    }
+
+    // walk down the trie using the same criteria as filter_by; returns the rating, and each round
+    // if 'explain' is set.
+    fn rating(&self, most: bool, explain: bool) -> (u128, Vec<Round>) {
+        let mut rounds: Vec<Round> = Vec::new();
+        let mut at = 0;
+        let mut value: u128 = 0;
+        for pos in 0..self.width {
+            let [zero, one] = self.nodes[at].children;
+            let (zeros, ones) = (self.count(zero), self.count(one));
+            let kept = if (zeros > 0 && ones > 0 && keep_ones(ones, zeros, most)) || zeros == 0 { 1 } else { 0 };
+            if explain && zeros + ones > 1 {
+                let mut survivors = Vec::new();
+                let next = self.nodes[at].children[kept].unwrap();
+                self.readings(next, (value << 1) | kept as u128, pos + 1, MAX_SURVIVORS_SHOWN, &mut survivors);
+                let left = self.nodes[next].count;
+                rounds.push(Round {stats: BitStats::new(pos, ones, zeros), kept, left, survivors});
+            }
+            at = self.nodes[at].children[kept].unwrap();
+            value = (value << 1) | kept as u128;
+        }
+        (value, rounds)
+    }
+}
+
+
+const MAX_SURVIVORS_SHOWN: usize = 8;
+
+
+fn print_rounds(report: &Report, name: &str, rounds: &[Round]) {
+    println!("{} rating rounds:", name);
+    for round in rounds.iter() {
+        let shown = round.survivors.iter()
+            .map(|&r| report.to_bin(r))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let more = if round.left > round.survivors.len() { " ..." } else { "" };
+        println!("  pos {:3}: ones {:6} zeros {:6}{} -> keep {}, {} left: {}{}",
+                 round.stats.pos, round.stats.ones, round.stats.zeros,
+                 if round.stats.tie { " (tie)" } else { "" },
+                 round.kept, round.left, shown, more);
     }
-    ns[0].clone()
 }
 
-pub fn day3_1() {
+
+fn read_report(options: &[String]) -> Result<Report, String> {
+    let file_name = utils::input_file(options, "./input/day03.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name)
+        .map_err(|e| format!("Reading failed: {}", e))?;
+    Report::parse(&lines)
+}
+
+
+pub fn day3_1(options: &[String]) {
     println!("First let's just get the binary test numbers:");
-    let r_strings = utils::read_file::<String>("./input/day03.txt");
-    match r_strings.iter().cloned().collect::<Result<Vec<String>, _>>() {
-        Ok(strings) => {
-            println!("Result value is: {:?}", strings);
-            let (ones, zeros) = bin_to_nums(aggregate_strings(&strings));
-            println!("Calculation {} * {} = {}", ones, zeros, ones * zeros);
+    match read_report(options) {
+        Ok(report) => {
+            println!("Report has {} readings of {} bits", report.readings.len(), report.width);
+            if utils::has_option(options, "--explain") {
+                println!("pos,ones,zeros,tie");
+                for stats in bit_stats(&report).iter() {
+                    println!("{},{},{},{}", stats.pos, stats.ones, stats.zeros, stats.tie);
+                }
+            }
+            let (gamma, epsilon) = gamma_epsilon(&report);
+            match gamma.checked_mul(epsilon) {
+                Some(v) => println!("Calculation {} * {} = {}", gamma, epsilon, v),
+                None => println!("Calculation {} * {} overflowed", gamma, epsilon),
+            }
         },
         Err(s) => println!("Parsing failed: {}", s),
     };
 }
 
 
-pub fn day3_2() {
+pub fn day3_2(options: &[String]) {
     println!("Calculate the Day3 part 2 numbers.");
-    let r_strings = utils::read_file::<String>("./input/day03.txt");
-    match r_strings.iter().cloned().collect::<Result<Vec<String>, _>>() {
-        Ok(strings) => {
-            println!("Result value is: {:?}", strings);
-            let o2 = filter_by(&strings, true);
-            let co2 = filter_by(&strings, false);
-            println!("o2: {}, co2: {}", o2, co2);
-            let (o2v, _) = bin_to_nums(o2);
-            let (co2v, _) = bin_to_nums(co2);
-            println!("Result: o2: {}, co2: {}, o2 * co2 = {}", o2v, co2v, o2v * co2v);
+    match read_report(options) {
+        Ok(report) => {
+            println!("Report has {} readings of {} bits", report.readings.len(), report.width);
+            let trie = Trie::new(&report);
+            let explain = utils::has_option(options, "--explain");
+            let mut ratings: Vec<u128> = Vec::new();
+            for (name, most) in [("Oxygen generator", true), ("CO2 scrubber", false)] {
+                let (rating, rounds) = trie.rating(most, explain);
+                if explain {
+                    print_rounds(&report, name, &rounds);
+                    println!("{} rating: {}", name, report.to_bin(rating));
+                }
+                // the sorted filter picks the same way, so it's a check on the trie.
+                let filtered = filter_by(&report, most);
+                if filtered != rating {
+                    println!("{} rating from the trie ({}) and the filter ({}) differ!",
+                             name, report.to_bin(rating), report.to_bin(filtered));
+                }
+                ratings.push(rating);
+            }
+            let (o2v, co2v) = (ratings[0], ratings[1]);
+            println!("o2: {}, co2: {}", report.to_bin(o2v), report.to_bin(co2v));
+            match o2v.checked_mul(co2v) {
+                Some(v) => println!("Result: o2: {}, co2: {}, o2 * co2 = {}", o2v, co2v, v),
+                None => println!("Result: o2: {}, co2: {}, o2 * co2 overflowed", o2v, co2v),
+            }
         },
         Err(s) => println!("Parsing failed: {}", s),
     };
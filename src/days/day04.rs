@@ -6,7 +6,7 @@
 
 
 use std::str::FromStr;
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 use crate::utils;
 
@@ -18,7 +18,8 @@ use thiserror::Error;
 //
 // 1. line of drawn numbers, split by ','
 // 2. A blank line.
-// 3. 5 lines of numbers split by spaces; 5 numbers in a row.
+// 3. Boards; a board is one or more lines of numbers split by spaces, ending at a blank line.
+//    Every line of a board must have the same number of numbers, but boards can be any size.
 
 #[derive(Error, Debug, Clone)]
 pub enum DecodeError {
@@ -36,10 +37,10 @@ impl FromStr for Draws {
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
         line
-            .split(",")
-            .map(|l| l.parse::<u32>())
+            .split(',')
+            .map(|l| l.trim().parse::<u32>())
             .collect::<Result<Vec<_>, _>>()
-            .map(|v| Self(v))
+            .map(Self)
             .map_err(|e| DecodeError::InvalidError(format!("Couldn't parse: {}", e)))
     }
 }
@@ -55,185 +56,360 @@ impl FromStr for Line {
         let rs = line
             .split_whitespace()
             .map(|l| l.parse::<u32>())
-            .take(5)
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| DecodeError::InvalidError(format!("Couldn't parse: {}", e)))?;
-        if rs.len() != 5 {
-            return Err(DecodeError::InvalidError(format!("Not enough numbers on line: {}", line)));
+        if rs.is_empty() {
+            return Err(DecodeError::InvalidError(format!("No numbers on line: {}", line)));
         }
         Ok(Self(rs))
     }
 }
 
 
-// 5 lines of 5 numbers.  One array arranged as 5 * lines of 5 numbers
-// contiguously.  To access line i, column j = i*5 + j
+// rows lines of cols numbers.  One array arranged as rows * lines of cols numbers
+// contiguously.  To access row i, column j = i*cols + j
 #[derive(Clone, Debug)]
-struct Board(Vec<u32>);
+struct Board {
+    rows: usize,
+    cols: usize,
+    cells: Vec<u32>,
+}
 
 
 impl Board {
 
-    fn at(&self, line: usize, column: usize) -> u32 {
-        self.0[line*5+column]
+    fn at(&self, row: usize, col: usize) -> u32 {
+        self.cells[row * self.cols + col]
     }
 
-    pub fn check_board_at_move(&self, so_far: &HashSet<u32>) -> bool {
-        for i in 0..=4 {
-            // check the line
-            if (0..=4).fold(true, |acc, j| acc && so_far.contains(&self.at(i, j))) {
-                return true;
-            }
-            // check the column
-            if (0..=4).fold(true, |acc, j| acc && so_far.contains(&self.at(j, i))) {
-                return true;
-            }
+    // the cell indexes of every line that wins; rows, columns and (optionally) the diagonals.
+    fn lines(&self, diagonals: bool) -> Vec<Vec<usize>> {
+        let mut lines: Vec<Vec<usize>> = Vec::new();
+        for row in 0..self.rows {
+            lines.push((0..self.cols).map(|col| row * self.cols + col).collect());
+        }
+        for col in 0..self.cols {
+            lines.push((0..self.rows).map(|row| row * self.cols + col).collect());
         }
-        false
+        if diagonals && self.has_diagonals() {
+            lines.push((0..self.rows).map(|i| i * self.cols + i).collect());
+            lines.push((0..self.rows).map(|i| i * self.cols + self.cols - 1 - i).collect());
+        }
+        lines
     }
 
-    pub fn sum_unmarked_numbers(&self, so_far: &HashSet<u32>) -> u32 {
-        self.0
-            .iter()
-            .fold(0, |acc, v| acc + if so_far.contains(v) {0} else {*v})
+    // diagonals only make sense on a square board
+    fn has_diagonals(&self) -> bool {
+        self.rows == self.cols
     }
 
-    // parse a block of 5 lines into a board
+    // parse a block of lines into a board
     pub fn parse_one<S>(lines: &[S]) -> Result<Self, DecodeError>
         where S: AsRef<str>
     {
-        if lines.len() != 5 {
-            return Err(DecodeError::InvalidError(format!("Not passed 5 board lines: {}", lines.len())));
-        }
-        // get a Vec<Line> parsed from 5 lines.
+        // get a Vec<Line> parsed from the lines.
         let rs = lines
             .iter()
             .map(|l| l.as_ref().parse::<Line>())
             .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| DecodeError::InvalidError(format!("Couldn't pase the board: {}", e)))?;
-        // convert the v lines into a single Vec<u32>
-        Ok(Board(
-            rs.iter()
-            .map(|l| l.0.clone())
-            .into_iter()
-            .flatten()
-            .collect()))
+            .map_err(|e| DecodeError::InvalidError(format!("Couldn't parse the board: {}", e)))?;
+        let rows = rs.len();
+        let cols = rs.first().map_or(0, |l| l.0.len());
+        if rows == 0 {
+            return Err(DecodeError::InvalidError("No lines for a board?".to_string()));
+        }
+        if let Some(l) = rs.iter().find(|l| l.0.len() != cols) {
+            return Err(DecodeError::InvalidError(
+                format!("Board line has {} numbers, not {}: {:?}", l.0.len(), cols, l.0)));
+        }
+        // convert the lines into a single Vec<u32>
+        let cells = rs.into_iter().flat_map(|l| l.0).collect();
+        Ok(Board {rows, cols, cells})
     }
 }
 
 
-// parse the lines; an empty line indicates the start of a board; so then take 5 lines and parse
-// the Lines from that tand then add them to the Board.
+// parse the lines; blank lines separate the boards, so collect lines until a blank one and parse
+// those as a Board.
 fn parse_boards<S>(lines: &[S]) -> Result<Vec<Board>, DecodeError>
     where S: AsRef<str>
 {
-    let length = lines.len();
-    let mut i: usize = 0;
-    let mut boards: Vec<Board> = Vec::with_capacity((length / 6) + 1);
-    while i < length {
-        if lines[i].as_ref().len() == 0 {
-            i += 1;
-            continue;
+    lines
+        .split(|l| l.as_ref().trim().is_empty())
+        .filter(|block| !block.is_empty())
+        .map(Board::parse_one)
+        .collect()
+}
+
+
+// the state of one board in a game; counts of marked cells for each row, column and diagonal
+// mean a win is spotted as soon as the number that completes it is marked.
+#[derive(Clone, Debug)]
+struct BoardState {
+    marked: Vec<bool>,
+    row_marks: Vec<usize>,
+    col_marks: Vec<usize>,
+    diag_marks: [usize; 2],
+    unmarked_sum: u64,
+    won: bool,
+}
+
+
+impl BoardState {
+
+    fn new(board: &Board) -> Self {
+        Self {
+            marked: vec![false; board.cells.len()],
+            row_marks: vec![0; board.rows],
+            col_marks: vec![0; board.cols],
+            diag_marks: [0; 2],
+            unmarked_sum: board.cells.iter().map(|&v| v as u64).sum(),
+            won: false,
         }
-        // pass the next 5 lines to parse_a_board
-        if i + 4 >= length {
-            return Err(DecodeError::InvalidError("Insufficient lines for a board?".to_string()));
+    }
+}
+
+
+/// When a board finished, and its score.
+#[derive(Clone, Debug)]
+struct Finish {
+    board: usize,
+    turn: usize,       // the index of the draw that won it
+    draw: u32,
+    score: u64,
+}
+
+
+// A game of bingo: 'index' maps a number to every (board, cell) it appears in, so a draw only
+// touches the cells that it marks.
+#[derive(Clone, Debug)]
+struct Game<'a> {
+    boards: &'a [Board],
+    diagonals: bool,
+    index: HashMap<u32, Vec<(usize, usize)>>,
+    states: Vec<BoardState>,
+    turn: usize,
+    in_play: usize,
+}
+
+
+impl<'a> Game<'a> {
+
+    fn new(boards: &'a [Board], diagonals: bool) -> Self {
+        let mut index: HashMap<u32, Vec<(usize, usize)>> = HashMap::new();
+        for (b, board) in boards.iter().enumerate() {
+            for (c, &v) in board.cells.iter().enumerate() {
+                index.entry(v).or_default().push((b, c));
+            }
         }
-        let board = Board::parse_one(&lines[i..i+5])?;
-        boards.push(board);
-        i += 5;
+        let states = boards.iter().map(BoardState::new).collect();
+        Self {boards, diagonals, index, states, turn: 0, in_play: boards.len()}
+    }
+
+    // mark a drawn number on every board, returning the boards that won with it.  A number can be
+    // on a board more than once, so every cell with it is marked before any board is scored.
+    fn draw(&mut self, draw: u32) -> Vec<Finish> {
+        let mut completed: Vec<usize> = Vec::new();
+        if let Some(cells) = self.index.get(&draw) {
+            for &(b, c) in cells.iter() {
+                let board = &self.boards[b];
+                let state = &mut self.states[b];
+                if state.won || state.marked[c] {
+                    continue;
+                }
+                state.marked[c] = true;
+                state.unmarked_sum -= draw as u64;
+                let (row, col) = (c / board.cols, c % board.cols);
+                state.row_marks[row] += 1;
+                state.col_marks[col] += 1;
+                let mut complete = state.row_marks[row] == board.cols || state.col_marks[col] == board.rows;
+                if self.diagonals && board.has_diagonals() {
+                    if row == col {
+                        state.diag_marks[0] += 1;
+                        complete |= state.diag_marks[0] == board.rows;
+                    }
+                    if row + col == board.cols - 1 {
+                        state.diag_marks[1] += 1;
+                        complete |= state.diag_marks[1] == board.rows;
+                    }
+                }
+                if complete && !completed.contains(&b) {
+                    completed.push(b);
+                }
+            }
+        }
+        let mut finishes: Vec<Finish> = Vec::with_capacity(completed.len());
+        for b in completed {
+            let state = &mut self.states[b];
+            state.won = true;
+            self.in_play -= 1;
+            finishes.push(Finish {board: b, turn: self.turn, draw, score: state.unmarked_sum * draw as u64});
+        }
+        self.turn += 1;
+        finishes
     }
-    Ok(boards)
 }
 
 
-// finds the first board that has won (check_board_at_move).
-fn check_for_a_winning_board(so_far: &HashSet<u32>, boards: &[Board]) -> Option<usize> {
-    for (i, board) in boards.iter().enumerate() {
-        if board.check_board_at_move(so_far) {
-            // return the index of the board that has a line or column
-            return Some(i);
+// play all the draws, returning every board that finished in the order that they won.
+fn play(draws: &Draws, boards: &[Board], diagonals: bool) -> Vec<Finish> {
+    let mut game = Game::new(boards, diagonals);
+    let mut finishes: Vec<Finish> = Vec::with_capacity(boards.len());
+    for &draw in draws.0.iter() {
+        finishes.extend(game.draw(draw));
+        if game.in_play == 0 {
+            break;
         }
     }
-    None
+    finishes
+}
+
+
+// --- replay and analysis
+
+fn print_board(game: &Game, b: usize) {
+    let board = &game.boards[b];
+    let state = &game.states[b];
+    println!("  Board {}{}:", b, if state.won { " (won)" } else { "" });
+    for row in 0..board.rows {
+        let line = (0..board.cols)
+            .map(|col| {
+                let v = board.at(row, col);
+                if state.marked[row * board.cols + col] { format!("[{:2}]", v) } else { format!(" {:2} ", v) }
+            })
+            .collect::<String>();
+        println!("   {}", line.trim_end());
+    }
 }
 
 
-// find the winning board index using the draws in sequence until we have a winning board
-fn find_first_winning_board(draws: &Draws, boards: &[Board]) -> u32 {
-    let mut so_far: HashSet<u32> = HashSet::with_capacity(draws.0.len());
+// play the draws one by one showing the marked state of the chosen boards.
+fn replay(draws: &Draws, boards: &[Board], chosen: &[usize], diagonals: bool) {
+    let mut game = Game::new(boards, diagonals);
     for &draw in draws.0.iter() {
-        so_far.insert(draw);
-        if let Some(index) = check_for_a_winning_board(&so_far, boards) {
-            // sum up the unmarked numbers
-            let sum = boards[index].sum_unmarked_numbers(&so_far);
-            // return the full sum
-            return sum * draw;
+        let finishes = game.draw(draw);
+        println!("Draw {} is {}; {} boards still in play", game.turn, draw, game.in_play);
+        for f in finishes.iter() {
+            println!("  Board {} wins, score {}", f.board, f.score);
+        }
+        for &b in chosen.iter() {
+            print_board(&game, b);
+        }
+        if game.in_play == 0 || chosen.iter().all(|&b| game.states[b].won) {
+            break;
         }
     }
-    panic!("Should have found a winning board?")
 }
 
 
-// see if ALL the boards have won; returns the indexes of boards that haven't yet won.
-fn check_for_losing_boards(so_far: &HashSet<u32>, boards: &[Board]) -> Vec<usize> {
-    let mut result: Vec<usize> = Vec::with_capacity(boards.len());
-    for (i, board) in boards.iter().enumerate() {
-        if !board.check_board_at_move(so_far) {
-            // add a board index if the board is not complete
-            result.push(i);
+// Find the shortest set of draws that makes the chosen board win before any other board.  The
+// draws can come in any order, but have to be numbers that are in the draws.  A line of the
+// chosen board works if no line of another board only uses numbers from it, as otherwise that
+// board would win first (or at the same time).  Returns the numbers in the order they are in the
+// draws, or None if the board can never win first.
+fn analyse_board(draws: &Draws, boards: &[Board], chosen: usize, diagonals: bool) -> Option<Vec<u32>> {
+    let order: HashMap<u32, usize> = draws.0.iter().enumerate().rev().map(|(i, &d)| (d, i)).collect();
+    let board = &boards[chosen];
+    let mut best: Option<Vec<u32>> = None;
+    for line in board.lines(diagonals) {
+        let mut numbers = line.iter().map(|&c| board.cells[c]).collect::<Vec<_>>();
+        numbers.sort_by_key(|n| order.get(n));
+        numbers.dedup();
+        if numbers.iter().any(|n| !order.contains_key(n)) {
+            continue;
+        }
+        let beaten = boards.iter()
+            .enumerate()
+            .filter(|&(b, _)| b != chosen)
+            .any(|(_, other)| other.lines(diagonals)
+                .iter()
+                .any(|l| l.iter().all(|&c| numbers.contains(&other.cells[c]))));
+        if !beaten && best.as_ref().is_none_or(|b| numbers.len() < b.len()) {
+            best = Some(numbers);
         }
     }
-    result
+    best
 }
 
 
-// find the winning board index using the draws in sequence until we have a winning board
-fn find_last_winning_board(draws: &Draws, boards: &[Board]) -> u32 {
-    let mut so_far: HashSet<u32> = HashSet::with_capacity(draws.0.len());
-    let mut last_round: Vec<usize> = vec![];
-    for &draw in draws.0.iter() {
-        so_far.insert(draw);
-        let round = check_for_losing_boards(&so_far, boards);
-        if round.len() == 0 {
-            // we've just had the last board.
-            if last_round.len() != 1 {
-                panic!("last round wasn't a single board when the final board was won??");
-            }
-            // sum up the unmarked numbers
-            let sum = boards[last_round[0]].sum_unmarked_numbers(&so_far);
-            // return the full sum
-            return sum * draw;
+// parse "1,2,3" into board indexes, checking they exist.
+fn parse_board_list(list: &str, boards: &[Board]) -> Result<Vec<usize>, String> {
+    list.split(',')
+        .map(|b| match b.trim().parse::<usize>() {
+            Ok(b) if b < boards.len() => Ok(b),
+            Ok(b) => Err(format!("There is no board {}; there are {} boards", b, boards.len())),
+            Err(e) => Err(format!("'{}' isn't a board number: {}", b, e)),
+        })
+        .collect()
+}
+
+
+// --replay <boards> and --analyse <board> can be used with either part.
+fn run_extras(options: &[String], draws: &Draws, boards: &[Board], diagonals: bool) {
+    if let Some(list) = utils::option_value::<String>(options, "--replay").expect("Bad --replay option") {
+        match parse_board_list(&list, boards) {
+            Ok(chosen) => replay(draws, boards, &chosen, diagonals),
+            Err(e) => println!("{}", e),
+        }
+    }
+    if let Some(list) = utils::option_value::<String>(options, "--analyse").expect("Bad --analyse option") {
+        match parse_board_list(&list, boards) {
+            Ok(chosen) => for b in chosen {
+                match analyse_board(draws, boards, b, diagonals) {
+                    Some(numbers) => println!("Board {} wins first if the draws start with {:?}", b, numbers),
+                    None => println!("Board {} can never win first", b),
+                }
+            },
+            Err(e) => println!("{}", e),
         }
-        last_round = round;
     }
-    panic!("Should have found a winning board?")
 }
 
 
-pub fn day4_1() {
-    println!("Giant Squid bingo!");
-    let read_lines = utils::read_file::<String>("./input/day04.txt");
-    let lines = read_lines.iter().cloned().collect::<Result<Vec<_>, _>>().expect("Failed to read file");
+fn print_finishes(finishes: &[Finish], boards: &[Board]) {
+    println!("Finishing order:");
+    for (i, f) in finishes.iter().enumerate() {
+        println!("  {:3}: board {:3} ({}x{}) won on draw {:3} ({:3}), score {}",
+                 i + 1, f.board, boards[f.board].rows, boards[f.board].cols, f.turn + 1, f.draw, f.score);
+    }
+    if finishes.len() < boards.len() {
+        println!("  {} boards never won.", boards.len() - finishes.len());
+    }
+}
+
+
+fn read_game(options: &[String]) -> (Draws, Vec<Board>) {
+    let file_name = utils::input_file(options, "./input/day04.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Failed to read file");
+    let draws = lines[0].parse::<Draws>().expect("Couldn't parse the draws");
+    let boards = parse_boards(&lines[1..]).expect("Couldn't parse the boards");
+    (draws, boards)
+}
 
-    let draws = lines[0].parse::<Draws>();
-    //println!("Draws are {:?}", draws);
-    let boards = parse_boards(&lines[1..]);
-    //println!("Boards are: {:?}", boards);
-    let winner = find_first_winning_board(&draws.unwrap(), &boards.unwrap());
-    println!("Winner index: {}", winner);
+
+pub fn day4_1(options: &[String]) {
+    println!("Giant Squid bingo!");
+    let (draws, boards) = read_game(options);
+    let diagonals = utils::has_option(options, "--diagonals");
+    run_extras(options, &draws, &boards, diagonals);
+    let finishes = play(&draws, &boards, diagonals);
+    print_finishes(&finishes, &boards);
+    match finishes.first() {
+        Some(winner) => println!("Winner score: {}", winner.score),
+        None => println!("No board won!"),
+    }
 }
 
 
-pub fn day4_2() {
+pub fn day4_2(options: &[String]) {
     println!("Giant Squid bingo! Squid has to win.");
-    let read_lines = utils::read_file::<String>("./input/day04.txt");
-    let lines = read_lines.iter().cloned().collect::<Result<Vec<_>, _>>().expect("Failed to read file");
-
-    let draws = lines[0].parse::<Draws>();
-    //println!("Draws are {:?}", draws);
-    let boards = parse_boards(&lines[1..]);
-    //println!("Boards are: {:?}", boards);
-    let winner = find_last_winning_board(&draws.unwrap(), &boards.unwrap());
-    println!("Winner index: {}", winner);
+    let (draws, boards) = read_game(options);
+    let diagonals = utils::has_option(options, "--diagonals");
+    run_extras(options, &draws, &boards, diagonals);
+    let finishes = play(&draws, &boards, diagonals);
+    print_finishes(&finishes, &boards);
+    match finishes.last() {
+        Some(winner) => println!("Winner score: {}", winner.score),
+        None => println!("No board won!"),
+    }
 }
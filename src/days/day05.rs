@@ -7,6 +7,8 @@ use std::str::FromStr;
 use std::ops::Add;
 use std::ops::Sub;
 use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::time::Instant;
 
 use crate::utils;
 
@@ -98,7 +100,7 @@ impl FromStr for Line {
 
 impl Line {
     fn draw_iter(&self) -> LineIterator {
-        LineIterator::new(&self)
+        LineIterator::new(self)
     }
 }
 
@@ -157,7 +159,7 @@ impl Iterator for LineIterator {
 type Map = HashMap<(i32,i32), u32>;
 
 
-// draw a line, assuming that the start -> end is horiz or vertical.
+// draw a line; any slope works, although only the 45 degree ones hit every point exactly.
 fn draw_line(line: &Line, points: &mut Map) {
     //println!("Line: {:?}", line);
     for p in line.draw_iter() {
@@ -166,37 +168,486 @@ fn draw_line(line: &Line, points: &mut Map) {
     }
 }
 
-pub fn day5_1() {
-    println!("Day 5: Hydrothermal Venture");
-    let read_lines = utils::read_file::<Line>("./input/day05.txt");
-    let lines = read_lines.iter().cloned().collect::<Result<Vec<_>, _>>().expect("Failed to read file");
-    println!("Number lines: {}", lines.len());
-    let mut points = Map::new();
-    for line in lines.iter() {
+// --- Counting overlaps from the segments, rather than drawing every point.
+//
+// Every horizontal, vertical or 45 degree line lies on a line of one of four 'families', and its
+// points are (key, t) where key says which line of the family it is on and t is where along it.
+// Overlaps within a family are just overlapping intervals of t on the same key.  Overlaps between
+// families are crossing points, which are found with a sweep line after turning the pair of
+// families into horizontals and verticals.  Other slopes aren't supported as Bresenham doesn't
+// draw them on the exact line.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Family {
+    Horizontal,    // key = y, t = x
+    Vertical,      // key = x, t = y
+    Rising,        // y - x constant: key = y - x, t = x
+    Falling,       // x + y constant: key = x + y, t = x
+}
+
+const FAMILIES: [Family; 4] = [Family::Horizontal, Family::Vertical, Family::Rising, Family::Falling];
+
+
+impl Family {
+
+    fn key_t(&self, x: i64, y: i64) -> (i64, i64) {
+        match self {
+            Family::Horizontal => (y, x),
+            Family::Vertical => (x, y),
+            Family::Rising => (y - x, x),
+            Family::Falling => (x + y, x),
+        }
+    }
+
+    fn point(&self, key: i64, t: i64) -> (i64, i64) {
+        match self {
+            Family::Horizontal => (t, key),
+            Family::Vertical => (key, t),
+            Family::Rising => (t, t + key),
+            Family::Falling => (t, key - t),
+        }
+    }
+
+    fn of(line: &Line) -> Option<Family> {
         let (dx, dy) = (line.end.x - line.start.x, line.end.y - line.start.y);
-        if dx != 0 && dy != 0 {
-            println!("Ignoring line: {:?} as it is diagonal", line);
+        match (dx, dy) {
+            (_, 0) => Some(Family::Horizontal),
+            (0, _) => Some(Family::Vertical),
+            _ if dx == dy => Some(Family::Rising),
+            _ if dx == -dy => Some(Family::Falling),
+            _ => None,
+        }
+    }
+}
+
+
+// inclusive intervals of t, sorted and not touching.
+type Intervals = Vec<(i64, i64)>;
+
+
+// merge the intervals on one line, returning those covered at least once and at least twice.
+fn merge_intervals(intervals: &[(i64, i64)]) -> (Intervals, Intervals) {
+    let mut events: Vec<(i64, i32)> = Vec::with_capacity(intervals.len() * 2);
+    for &(t1, t2) in intervals.iter() {
+        events.push((t1, 1));
+        events.push((t2 + 1, -1));
+    }
+    events.sort_unstable();
+    let (mut once, mut twice): (Intervals, Intervals) = (Vec::new(), Vec::new());
+    let mut depth: i32 = 0;
+    let (mut once_start, mut twice_start) = (0, 0);
+    let mut i = 0;
+    while i < events.len() {
+        let t = events[i].0;
+        let before = depth;
+        while i < events.len() && events[i].0 == t {
+            depth += events[i].1;
+            i += 1;
+        }
+        if before < 1 && depth >= 1 { once_start = t; }
+        if before >= 1 && depth < 1 { once.push((once_start, t - 1)); }
+        if before < 2 && depth >= 2 { twice_start = t; }
+        if before >= 2 && depth < 2 { twice.push((twice_start, t - 1)); }
+    }
+    (once, twice)
+}
+
+
+fn in_intervals(intervals: &[(i64, i64)], t: i64) -> bool {
+    let i = intervals.partition_point(|&(_, t2)| t2 < t);
+    i < intervals.len() && intervals[i].0 <= t
+}
+
+
+// visit where horizontals (v, u1, u2) cross verticals (u, v1, v2), as (u, v).  If 'parity' is set
+// then only points where u and v are both odd or both even are real points.
+fn sweep_crossings<F>(horizontals: &[(i64, i64, i64)], verticals: &[(i64, i64, i64)], parity: bool, mut visit: F)
+    where F: FnMut(i64, i64)
+{
+    // events at the same u: add horizontals, then query verticals, then remove horizontals.
+    let mut events: Vec<(i64, u8, usize)> = Vec::with_capacity(horizontals.len() * 2 + verticals.len());
+    for (i, &(_, u1, u2)) in horizontals.iter().enumerate() {
+        events.push((u1, 0, i));
+        events.push((u2, 2, i));
+    }
+    for (i, &(u, _, _)) in verticals.iter().enumerate() {
+        events.push((u, 1, i));
+    }
+    events.sort_unstable();
+    let mut active: BTreeMap<i64, usize> = BTreeMap::new();
+    for &(u, kind, i) in events.iter() {
+        match kind {
+            0 => { *active.entry(horizontals[i].0).or_insert(0) += 1; },
+            1 => {
+                let (_, v1, v2) = verticals[i];
+                for (&v, _) in active.range(v1..=v2) {
+                    if !parity || (u - v) % 2 == 0 {
+                        visit(u, v);
+                    }
+                }
+            },
+            _ => {
+                let v = horizontals[i].0;
+                let count = active.get_mut(&v).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    active.remove(&v);
+                }
+            },
+        }
+    }
+}
+
+
+// the merged lines of each family: key -> (covered once intervals, covered twice intervals)
+type Merged = BTreeMap<(Family, i64), (Intervals, Intervals)>;
+
+
+// turn the covered intervals of two families into the horizontals and verticals of a sweep, and
+// visit where they cross in x, y.
+fn family_crossings<F>(merged: &Merged, a: Family, b: Family, mut visit: F)
+    where F: FnMut(i64, i64)
+{
+    // (u, v) coordinates: a is horizontal (v = key of a) and b is vertical (u = key of b).
+    // Along a line of a, the key of b changes by 1 per step of t, except rising vs falling where
+    // it changes by 2 and only every other (u, v) is a real point.
+    let to_uv = |x: i64, y: i64| (b.key_t(x, y).0, a.key_t(x, y).0);
+    let mut horizontals: Vec<(i64, i64, i64)> = Vec::new();
+    let mut verticals: Vec<(i64, i64, i64)> = Vec::new();
+    for (&(family, key), (once, _)) in merged.range((a, i64::MIN)..=(a, i64::MAX)) {
+        for &(t1, t2) in once.iter() {
+            let (p1, p2) = (family.point(key, t1), family.point(key, t2));
+            let (u1, v) = to_uv(p1.0, p1.1);
+            let (u2, _) = to_uv(p2.0, p2.1);
+            horizontals.push((v, u1.min(u2), u1.max(u2)));
+        }
+    }
+    for (&(family, key), (once, _)) in merged.range((b, i64::MIN)..=(b, i64::MAX)) {
+        for &(t1, t2) in once.iter() {
+            let (p1, p2) = (family.point(key, t1), family.point(key, t2));
+            let (u, v1) = to_uv(p1.0, p1.1);
+            let (_, v2) = to_uv(p2.0, p2.1);
+            verticals.push((u, v1.min(v2), v1.max(v2)));
+        }
+    }
+    let parity = a == Family::Rising && b == Family::Falling;
+    sweep_crossings(&horizontals, &verticals, parity, |u, v| {
+        let (x, y) = match (a, b) {
+            (Family::Horizontal, Family::Vertical) => (u, v),
+            (Family::Horizontal, Family::Rising) => (v - u, v),
+            (Family::Horizontal, Family::Falling) => (u - v, v),
+            (Family::Vertical, Family::Rising) => (v, u + v),
+            (Family::Vertical, Family::Falling) => (v, u - v),
+            _ => ((u - v) / 2, (u + v) / 2),    // rising (v = y - x) vs falling (u = x + y)
+        };
+        visit(x, y);
+    });
+}
+
+
+// is (x, y) covered by a line of the family, or by two or more lines if 'twice'?
+fn covers(merged: &Merged, family: Family, x: i64, y: i64, twice: bool) -> bool {
+    let (key, t) = family.key_t(x, y);
+    merged.get(&(family, key))
+        .is_some_and(|(once, more)| in_intervals(if twice { more } else { once }, t))
+}
+
+
+/// Count the points where at least two lines overlap, without drawing them.  Errors if a line
+/// isn't horizontal, vertical or at 45 degrees.
+fn count_overlaps_sweep(lines: &[Line]) -> Result<usize, DecodeError> {
+    let mut by_line: BTreeMap<(Family, i64), Vec<(i64, i64)>> = BTreeMap::new();
+    for line in lines.iter() {
+        let family = Family::of(line).ok_or_else(|| DecodeError::InvalidError(
+            format!("Line isn't horizontal, vertical or diagonal: {:?}", line)))?;
+        let (key, t1) = family.key_t(line.start.x as i64, line.start.y as i64);
+        let (_, t2) = family.key_t(line.end.x as i64, line.end.y as i64);
+        by_line.entry((family, key)).or_default().push((t1.min(t2), t1.max(t2)));
+    }
+    let merged: Merged = by_line.iter()
+        .map(|(&k, intervals)| (k, merge_intervals(intervals)))
+        .collect();
+    // overlaps along the same line
+    let mut count: usize = merged.values()
+        .map(|(_, twice)| twice.iter().map(|&(t1, t2)| (t2 - t1 + 1) as usize).sum::<usize>())
+        .sum();
+    // crossing points are overlaps too, counted as the sweeps find them.  A point can be found by
+    // several sweeps, so it's only looked at by the sweep of the first two families that cover
+    // it; and if it was already counted as an overlap along one or more lines then it must only
+    // be counted once.
+    for (i, &a) in FAMILIES.iter().enumerate() {
+        for &b in FAMILIES[i + 1..].iter() {
+            family_crossings(&merged, a, b, |x, y| {
+                let first = FAMILIES.iter()
+                    .take_while(|&&c| c != b)
+                    .all(|&c| c == a || !covers(&merged, c, x, y, false));
+                if !first {
+                    return;
+                }
+                match FAMILIES.iter().filter(|&&c| covers(&merged, c, x, y, true)).count() {
+                    0 => count += 1,
+                    counted => count -= counted - 1,
+                }
+            });
+        }
+    }
+    Ok(count)
+}
+
+
+fn count_overlaps_raster(lines: &[Line]) -> usize {
+    VentMap::draw(lines).count_at_least(2)
+}
+
+
+// count the points with at least 'threshold' lines.  Uses the sweep if asked (--sweep) and the
+// threshold is 2, falling back to drawing the lines if it can't.
+fn count_overlaps(lines: &[Line], threshold: u32, options: &[String]) -> usize {
+    if utils::has_option(options, "--sweep") {
+        if threshold != 2 {
+            println!("The sweep only counts overlaps of 2 or more, drawing the lines instead.");
         } else {
+            match count_overlaps_sweep(lines) {
+                Ok(num) => return num,
+                Err(e) => println!("Can't use the sweep, drawing the lines instead: {:?}", e),
+            }
+        }
+    }
+    VentMap::draw(lines).count_at_least(threshold)
+}
+
+
+// --- Analytics over the drawn vent map.
+
+/// Which lines to use; part 1 only uses the horizontal and vertical ones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LineFilter {
+    Axis,        // horizontal and vertical
+    Diagonal,    // only the 45 degree lines
+    All,         // every line, whatever the slope
+}
+
+
+impl FromStr for LineFilter {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "axis" => Ok(LineFilter::Axis),
+            "diagonal" => Ok(LineFilter::Diagonal),
+            "all" => Ok(LineFilter::All),
+            _ => Err(DecodeError::InvalidError(format!("Line filter is not one of 'axis', 'diagonal' or 'all': {}", s))),
+        }
+    }
+}
+
+
+impl LineFilter {
+
+    fn accepts(&self, line: &Line) -> bool {
+        let (dx, dy) = (line.end.x - line.start.x, line.end.y - line.start.y);
+        match self {
+            LineFilter::Axis => dx == 0 || dy == 0,
+            LineFilter::Diagonal => dx != 0 && dx.abs() == dy.abs(),
+            LineFilter::All => true,
+        }
+    }
+}
+
+
+// the map of how many lines cover each point.
+#[derive(Clone, Debug, Default)]
+struct VentMap(Map);
+
+
+impl VentMap {
+
+    fn draw(lines: &[Line]) -> Self {
+        let mut points = Map::new();
+        for line in lines.iter() {
             draw_line(line, &mut points);
         }
+        Self(points)
+    }
+
+    fn count_at_least(&self, threshold: u32) -> usize {
+        self.0.values().filter(|&v| *v >= threshold).count()
+    }
+
+    // how many points are covered by each number of lines.
+    fn histogram(&self) -> BTreeMap<u32, usize> {
+        let mut histogram: BTreeMap<u32, usize> = BTreeMap::new();
+        for &v in self.0.values() {
+            *histogram.entry(v).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    // the k points with the most lines; ties are in (x, y) order so the answer is repeatable.
+    fn hottest(&self, k: usize) -> Vec<(PointXY, u32)> {
+        let mut points = self.0.iter().map(|(&(x, y), &v)| (PointXY {x, y}, v)).collect::<Vec<_>>();
+        points.sort_by(|(p1, v1), (p2, v2)| v2.cmp(v1).then((p1.x, p1.y).cmp(&(p2.x, p2.y))));
+        points.truncate(k);
+        points
+    }
+
+    // the (min, max) corners of all the drawn points.
+    fn bounding_box(&self) -> Option<(PointXY, PointXY)> {
+        let mut points = self.0.keys();
+        let &(x, y) = points.next()?;
+        let (mut min, mut max) = (PointXY {x, y}, PointXY {x, y});
+        for &(x, y) in points {
+            min = PointXY {x: min.x.min(x), y: min.y.min(y)};
+            max = PointXY {x: max.x.max(x), y: max.y.max(y)};
+        }
+        Some((min, max))
     }
-    // now count up the number of points in the hashmap that are more than 1.
-    //println!("points: {:?}", points);
-    let num = points.values().filter(|&v| *v > 1).count();
-    println!("Found {} points that overlap.", num);
 }
 
-pub fn day5_2() {
-    println!("Day 5: Hydrothermal Venture - diagonal lines");
-    let read_lines = utils::read_file::<Line>("./input/day05.txt");
-    let lines = read_lines.iter().cloned().collect::<Result<Vec<_>, _>>().expect("Failed to read file");
-    println!("Number lines: {}", lines.len());
-    let mut points = Map::new();
-    for line in lines.iter() {
-        draw_line(line, &mut points);
+
+// print the analytics asked for: --histogram, --top <k> and --bbox
+fn print_analytics(lines: &[Line], options: &[String]) {
+    let top = utils::option_value::<usize>(options, "--top").expect("Bad --top option");
+    let histogram = utils::has_option(options, "--histogram");
+    let bbox = utils::has_option(options, "--bbox");
+    if top.is_none() && !histogram && !bbox {
+        return;
     }
-    // now count up the number of points in the hashmap that are more than 1.
-    //println!("points: {:?}", points);
-    let num = points.values().filter(|&v| *v > 1).count();
-    println!("Found {} points that overlap.", num);
+    let map = VentMap::draw(lines);
+    if histogram {
+        println!("Lines  Points");
+        for (v, n) in map.histogram().iter() {
+            println!("{:5}  {}", v, n);
+        }
+    }
+    if let Some(k) = top {
+        println!("Hottest {} points:", k);
+        for (p, v) in map.hottest(k).iter() {
+            println!("  ({}, {}) has {} lines", p.x, p.y, v);
+        }
+    }
+    if bbox {
+        match map.bounding_box() {
+            Some((min, max)) => println!("Bounding box: ({}, {}) -> ({}, {})", min.x, min.y, max.x, max.y),
+            None => println!("Bounding box: no points drawn"),
+        }
+    }
+}
+
+
+// --- benchmarking on generated lines
+
+// a small xorshift generator so that the generated lines are repeatable.
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+
+fn generate_lines(num: usize, max_coord: i32, seed: u64) -> Vec<Line> {
+    let mut state = seed.max(1);
+    let mut lines: Vec<Line> = Vec::with_capacity(num);
+    let range = max_coord as u64 + 1;
+    for _ in 0..num {
+        let x = (next_random(&mut state) % range) as i32;
+        let y = (next_random(&mut state) % range) as i32;
+        let len = (next_random(&mut state) % range) as i32;
+        let start = PointXY {x, y};
+        let end = match next_random(&mut state) % 4 {
+            0 => PointXY {x: (x + len).min(max_coord), y},
+            1 => PointXY {x, y: (y + len).min(max_coord)},
+            2 => { let d = len.min(max_coord - x).min(max_coord - y); PointXY {x: x + d, y: y + d} },
+            _ => { let d = len.min(max_coord - x).min(y); PointXY {x: x + d, y: y - d} },
+        };
+        lines.push(Line {start, end});
+    }
+    lines
+}
+
+
+// the number of points that drawing the lines would visit
+fn raster_size(lines: &[Line]) -> u64 {
+    lines.iter()
+        .map(|l| (l.end.x - l.start.x).abs().max((l.end.y - l.start.y).abs()) as u64 + 1)
+        .sum()
+}
+
+
+const MAX_RASTER_BENCH: u64 = 50_000_000;
+
+// the sweep's time grows with the number of crossings, and random long lines cross about n^2 / 10
+// times, so 20,000 lines already takes several seconds.
+const MAX_SWEEP_BENCH: usize = 20_000;
+
+
+fn bench(num: usize, max_coord: i32) {
+    if num > MAX_SWEEP_BENCH {
+        println!("Benchmark: at most {} lines, not {}", MAX_SWEEP_BENCH, num);
+        return;
+    }
+    let lines = generate_lines(num, max_coord, 2021);
+    println!("Benchmark: {} lines with coordinates up to {}", num, max_coord);
+    let start = Instant::now();
+    let sweep = count_overlaps_sweep(&lines).expect("generated lines are all supported");
+    println!("  sweep:  {} overlaps in {:?}", sweep, start.elapsed());
+    let size = raster_size(&lines);
+    if size > MAX_RASTER_BENCH {
+        println!("  raster: skipped, it would draw {} points", size);
+        return;
+    }
+    let start = Instant::now();
+    let raster = count_overlaps_raster(&lines);
+    println!("  raster: {} overlaps in {:?}", raster, start.elapsed());
+    if raster != sweep {
+        println!("  The sweep and raster counts differ!");
+    }
+}
+
+
+// --bench <num lines> runs the benchmark rather than the puzzle; --max-coord sets the size.
+fn run_bench(options: &[String]) -> bool {
+    match utils::option_value::<usize>(options, "--bench").expect("Bad --bench option") {
+        Some(num) => {
+            let max_coord = utils::option_value::<i32>(options, "--max-coord")
+                .expect("Bad --max-coord option")
+                .unwrap_or(1_000_000);
+            bench(num, max_coord);
+            true
+        },
+        None => false,
+    }
+}
+
+
+fn run(options: &[String], default_filter: LineFilter) {
+    if run_bench(options) {
+        return;
+    }
+    let filter = utils::option_value::<LineFilter>(options, "--lines")
+        .expect("Bad --lines option")
+        .unwrap_or(default_filter);
+    let threshold = utils::option_value::<u32>(options, "--threshold")
+        .expect("Bad --threshold option")
+        .unwrap_or(2);
+    let file_name = utils::input_file(options, "./input/day05.txt");
+    let read_lines = utils::read_file::<Line>(&file_name);
+    let lines = read_lines.iter().cloned().collect::<Result<Vec<_>, _>>().expect("Failed to read file");
+    let lines = lines.into_iter().filter(|l| filter.accepts(l)).collect::<Vec<_>>();
+    println!("Number lines using filter {:?}: {}", filter, lines.len());
+    let num = count_overlaps(&lines, threshold, options);
+    println!("Found {} points with at least {} lines.", num, threshold);
+    print_analytics(&lines, options);
+}
+
+
+pub fn day5_1(options: &[String]) {
+    println!("Day 5: Hydrothermal Venture");
+    run(options, LineFilter::Axis);
+}
+
+pub fn day5_2(options: &[String]) {
+    println!("Day 5: Hydrothermal Venture - diagonal lines");
+    run(options, LineFilter::All);
 }
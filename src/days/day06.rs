@@ -4,23 +4,75 @@
 
 // A massive school of glowing lanternfish swims past. They must spawn quickly to reach such large numbers - maybe exponentially quickly? You should model their growth rate to be sure.
 
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::{Add, Mul};
+
 use crate::utils;
 
 
-type Fish = [u64; 9];
+// counts of fish by their timer ('age'); fish[0] are about to spawn.
+type Fish = Vec<u64>;
+
+// the most days that count_stepped will step through.
+const MAX_STEPPED_DAYS: u64 = 1_000_000;
+
+// (cycle, newborn delay) models that the matrix is checked against stepping for.
+const MODEL_CHECKS: [(usize, usize); 4] = [(7, 2), (7, 0), (3, 1), (1, 0)];
+
+// (day, total) for the test input (3,4,3,1,2) from the puzzle.
+const CHECKPOINTS: [(u64, u64); 3] = [(18, 26), (80, 5934), (256, 26984457539)];
+
+
+/// The life cycle of a lanternfish.  A fish spawns every 'cycle' days, and a newborn takes
+/// 'newborn_delay' extra days before its first spawn.  If there's a 'max_age' then a fish dies
+/// once it has lived that many days (fish alive at the start count as newborn on day 0).
+#[derive(Clone, Debug)]
+struct LifeCycle {
+    cycle: usize,
+    newborn_delay: usize,
+    max_age: Option<usize>,
+}
+
+
+impl Default for LifeCycle {
+    fn default() -> Self {
+        Self {cycle: 7, newborn_delay: 2, max_age: None}
+    }
+}
+
+
+impl LifeCycle {
+
+    fn from_options(options: &[String]) -> Result<Self, String> {
+        let default = Self::default();
+        let cycle = utils::option_value::<usize>(options, "--cycle")?.unwrap_or(default.cycle);
+        let newborn_delay = utils::option_value::<usize>(options, "--delay")?.unwrap_or(default.newborn_delay);
+        let max_age = utils::option_value::<usize>(options, "--max-age")?;
+        if cycle == 0 {
+            return Err("The spawn cycle must be at least 1 day".to_string());
+        }
+        Ok(Self {cycle, newborn_delay, max_age})
+    }
+
+    // the number of timer values; a newborn starts on the last one.
+    fn timers(&self) -> usize {
+        self.cycle + self.newborn_delay
+    }
+}
 
 
-fn parse_fish<S: AsRef<str>>(line: S) -> Result<Fish, String> {
+fn parse_fish<S: AsRef<str>>(line: S, model: &LifeCycle) -> Result<Fish, String> {
     let fishes = line.as_ref()
-        .split(",")
+        .split(',')
         .map(|l| l.parse::<usize>()).collect::<Result<Vec<_>, _>>()
         .map_err(|e| format!("Parser error: {}", e))?;
-    println!("Fishes = {:?}", fishes);
 
-    let mut fish: Fish = [0; 9];
+    let mut fish: Fish = vec![0; model.timers()];
     for f in fishes {
-        if f > 8 {
-            return Err(format!("A fish's age was not between 0 and 8: {}", f));
+        if f >= model.timers() {
+            return Err(format!("A fish's age was not between 0 and {}: {}", model.timers() - 1, f));
         }
         fish[f] += 1;
     }
@@ -28,50 +80,413 @@ fn parse_fish<S: AsRef<str>>(line: S) -> Result<Fish, String> {
 }
 
 
+// The fish by how many days they've lived and their timer.  Without a max age, how long they've
+// lived doesn't matter, so there's just one row.
+#[derive(Clone, Debug)]
+struct Population {
+    rows: Vec<Fish>,
+}
+
+
+impl Population {
+
+    fn new(fish: &Fish, model: &LifeCycle) -> Self {
+        let mut rows = vec![vec![0; model.timers()]; model.max_age.map_or(1, |m| m + 1)];
+        rows[0] = fish.clone();
+        Self {rows}
+    }
+
+    // the counts for each timer, whatever their age.
+    fn by_timer(&self) -> Fish {
+        let mut fish = vec![0; self.rows[0].len()];
+        for row in self.rows.iter() {
+            for (t, &n) in row.iter().enumerate() {
+                fish[t] += n;
+            }
+        }
+        fish
+    }
+
+    fn total(&self) -> u64 {
+        self.rows.iter().flatten().sum()
+    }
+
+    // age the population by a day; None if the counts overflow a u64.
+    fn age(&self, model: &LifeCycle) -> Option<Self> {
+        let timers = model.timers();
+        let mut rows = vec![vec![0u64; timers]; self.rows.len()];
+        for (lived, row) in self.rows.iter().enumerate() {
+            // fish that have lived past the max age die
+            let next_lived = if model.max_age.is_some() { lived + 1 } else { 0 };
+            if next_lived >= self.rows.len() {
+                continue;
+            }
+            for (timer, &n) in row.iter().enumerate() {
+                if timer == 0 {
+                    rows[next_lived][model.cycle - 1] = rows[next_lived][model.cycle - 1].checked_add(n)?;
+                    rows[0][timers - 1] = rows[0][timers - 1].checked_add(n)?;
+                } else {
+                    rows[next_lived][timer - 1] = rows[next_lived][timer - 1].checked_add(n)?;
+                }
+            }
+        }
+        Some(Self {rows})
+    }
+}
+
+
 // age a generation of fish
-fn age_fish(gen: &Fish) -> Fish {
-    let mut next_gen: Fish = [0; 9];
-    for i in 1..=8 {
-        next_gen[i-1] = gen[i];
+fn age_fish(gen: &Fish, model: &LifeCycle) -> Option<Fish> {
+    Population::new(gen, &LifeCycle {max_age: None, ..model.clone()})
+        .age(model)
+        .map(|p| p.by_timer())
+}
+
+
+// write the population each day from 0 to 'days' as csv: day, total and the count for each
+// timer.  It steps a day at a time, so it's capped at MAX_STEPPED_DAYS too.
+fn write_time_series<W: Write>(fish: &Fish, days: u64, model: &LifeCycle, out: &mut W) -> Result<(), String> {
+    if days > MAX_STEPPED_DAYS {
+        return Err(format!("The time series steps a day at a time, so it can't go past {} days", MAX_STEPPED_DAYS));
     }
-    next_gen[8] = gen[0];
-    next_gen[6] += gen[0];
-    next_gen
+    let write_err = |e: std::io::Error| format!("Couldn't write the csv: {}", e);
+    let header = (0..model.timers()).map(|t| format!(",timer{}", t)).collect::<String>();
+    writeln!(out, "day,total{}", header).map_err(write_err)?;
+    let mut population = Population::new(fish, model);
+    for day in 0..=days {
+        if day > 0 {
+            population = population.age(model)
+                .ok_or_else(|| format!("The count overflowed on day {}", day))?;
+        }
+        let buckets = population.by_timer().iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        writeln!(out, "{},{},{}", day, population.total(), buckets.join(",")).map_err(write_err)?;
+    }
+    out.flush().map_err(write_err)
 }
 
-pub fn day6_1() {
-    println!("Day 5: Hydrothermal Venture");
-    let lines = utils::read_file_single_result::<String>("./input/day06.txt")
-        .expect("Error reading the file");
-    if lines.len() != 1 {
-        println!("Input file has either no lines or more than 1: {}", lines.len());
-        return;
+
+// --- Counting fish for any number of days.
+//
+// A day of ageing is a linear map on the ages, so N days is the transition matrix to the power
+// N, found by squaring.  The counts get big quickly (past u64 after about 400 days) so the
+// matrix is generic over the number type: either a BigUint or counts modulo some number.
+
+/// A small arbitrary precision unsigned integer; just enough to add, multiply and print.
+#[derive(Clone, Debug, PartialEq)]
+struct BigUint(Vec<u32>);     // little endian base 2^32 digits, no trailing zeros
+
+
+impl BigUint {
+
+    fn from_u64(v: u64) -> Self {
+        let mut n = Self(vec![v as u32, (v >> 32) as u32]);
+        n.trim();
+        n
+    }
+
+    fn trim(&mut self) {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // divide in place by a small number, returning the remainder.
+    fn div_rem_small(&mut self, d: u32) -> u32 {
+        let mut rem: u64 = 0;
+        for digit in self.0.iter_mut().rev() {
+            let v = (rem << 32) | *digit as u64;
+            *digit = (v / d as u64) as u32;
+            rem = v % d as u64;
+        }
+        self.trim();
+        rem as u32
+    }
+}
+
+
+impl Add for BigUint {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (long, short) = if self.0.len() >= other.0.len() { (self.0, other.0) } else { (other.0, self.0) };
+        let mut result: Vec<u32> = Vec::with_capacity(long.len() + 1);
+        let mut carry: u64 = 0;
+        for (i, &d) in long.iter().enumerate() {
+            let v = d as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+            result.push(v as u32);
+            carry = v >> 32;
+        }
+        if carry > 0 {
+            result.push(carry as u32);
+        }
+        Self(result)
+    }
+}
+
+
+impl Mul for BigUint {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        if self.is_zero() || other.is_zero() {
+            return Self(vec![]);
+        }
+        let mut result: Vec<u32> = vec![0; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry: u64 = 0;
+            for (j, &b) in other.0.iter().enumerate() {
+                let v = result[i + j] as u64 + a as u64 * b as u64 + carry;
+                result[i + j] = v as u32;
+                carry = v >> 32;
+            }
+            result[i + other.0.len()] = carry as u32;
+        }
+        let mut n = Self(result);
+        n.trim();
+        n
     }
-    let mut fish = parse_fish(&lines[0]).expect("Failed to parse fish");
-    println!("Fish are: {:?}", fish);
-    for i in 1..=80 {
-        fish = age_fish(&fish);
-        if i == 18  || i == 80 {
-            println!("Day {}, fish = {:?}, total = {}", i, fish, fish.iter().sum::<u64>());
+}
+
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // peel off 9 decimal digits at a time
+        let mut n = self.clone();
+        let mut chunks: Vec<u32> = Vec::new();
+        while !n.is_zero() {
+            chunks.push(n.div_rem_small(1_000_000_000));
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// A count modulo some number, for when only the remainder is wanted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+
+impl Add for Modular {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let value = ((self.value as u128 + other.value as u128) % self.modulus as u128) as u64;
+        Self {value, modulus: self.modulus}
+    }
+}
+
+
+impl Mul for Modular {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let value = ((self.value as u128 * other.value as u128) % self.modulus as u128) as u64;
+        Self {value, modulus: self.modulus}
+    }
+}
+
+
+impl fmt::Display for Modular {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (mod {})", self.value, self.modulus)
+    }
+}
+
+
+type Matrix<T> = Vec<Vec<T>>;
+
+
+fn mat_mul<T>(a: &Matrix<T>, b: &Matrix<T>, zero: &T) -> Matrix<T>
+    where T: Clone + Add<Output=T> + Mul<Output=T>
+{
+    let n = a.len();
+    let mut result: Matrix<T> = vec![vec![zero.clone(); n]; n];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            for k in 0..n {
+                *cell = cell.clone() + a[i][k].clone() * b[k][j].clone();
+            }
         }
     }
+    result
 }
 
 
-pub fn day6_2() {
-    println!("Day 5: Hydrothermal Venture");
-    let lines = utils::read_file_single_result::<String>("./input/day06.txt")
+// raise the matrix to the power by repeated squaring.
+fn mat_pow<T>(m: &Matrix<T>, mut power: u64, zero: &T, one: &T) -> Matrix<T>
+    where T: Clone + Add<Output=T> + Mul<Output=T>
+{
+    let n = m.len();
+    let mut result: Matrix<T> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { one.clone() } else { zero.clone() }).collect())
+        .collect();
+    let mut base = m.clone();
+    while power > 0 {
+        if power & 1 == 1 {
+            result = mat_mul(&result, &base, zero);
+        }
+        power >>= 1;
+        if power > 0 {
+            base = mat_mul(&base, &base, zero);
+        }
+    }
+    result
+}
+
+
+// the matrix for one day of age_fish: row i says which ages become age i the next day.  This
+// doesn't do max ages, which need the slower Population.
+fn transition_matrix<T: Clone + Add<Output=T>>(model: &LifeCycle, zero: &T, one: &T) -> Matrix<T> {
+    let timers = model.timers();
+    let mut m: Matrix<T> = vec![vec![zero.clone(); timers]; timers];
+    for (i, row) in m.iter_mut().enumerate().take(timers - 1) {
+        row[i + 1] = one.clone();
+    }
+    // with no newborn delay the parent and its newborn both go to cycle - 1, so add them.
+    m[timers - 1][0] = one.clone();
+    m[model.cycle - 1][0] = m[model.cycle - 1][0].clone() + one.clone();
+    m
+}
+
+
+// the total number of fish after 'days' days.
+fn count_fish_after<T, F>(fish: &Fish, days: u64, model: &LifeCycle, zero: T, one: T, from_u64: F) -> T
+    where T: Clone + Add<Output=T> + Mul<Output=T>,
+          F: Fn(u64) -> T,
+{
+    let m = mat_pow(&transition_matrix(model, &zero, &one), days, &zero, &one);
+    let mut total = zero.clone();
+    for row in m.iter() {
+        for (age, cell) in row.iter().enumerate() {
+            total = total + cell.clone() * from_u64(fish[age]);
+        }
+    }
+    total
+}
+
+
+fn count_big(fish: &Fish, days: u64, model: &LifeCycle) -> BigUint {
+    count_fish_after(fish, days, model, BigUint::from_u64(0), BigUint::from_u64(1), BigUint::from_u64)
+}
+
+
+fn count_modular(fish: &Fish, days: u64, model: &LifeCycle, modulus: u64) -> Modular {
+    let m = |value: u64| Modular {value: value % modulus, modulus};
+    count_fish_after(fish, days, model, m(0), m(1), m)
+}
+
+
+// with a max age, step the population a day at a time.  That's O(days), so it's capped at
+// MAX_STEPPED_DAYS; use the matrix (no max age) for more days than that.
+fn count_stepped(fish: &Fish, days: u64, model: &LifeCycle) -> Result<u64, String> {
+    if days > MAX_STEPPED_DAYS {
+        return Err(format!("A max age steps a day at a time, so it can't go past {} days", MAX_STEPPED_DAYS));
+    }
+    let mut population = Population::new(fish, model);
+    for day in 1..=days {
+        population = population.age(model)
+            .ok_or_else(|| format!("The count overflowed on day {}", day))?;
+    }
+    Ok(population.total())
+}
+
+
+// make sure the matrix gives the same answers as the puzzle, and as stepping a day at a time,
+// for the test input.
+fn check_regressions() {
+    let model = LifeCycle::default();
+    let fish = parse_fish("3,4,3,1,2", &model).expect("Failed to parse the test fish");
+    let mut stepped = fish.clone();
+    let mut day: u64 = 0;
+    for &(days, expected) in CHECKPOINTS.iter() {
+        while day < days {
+            stepped = age_fish(&stepped, &model).expect("The test fish overflowed");
+            day += 1;
+        }
+        let total = count_big(&fish, days, &model);
+        if total == BigUint::from_u64(expected) && expected == stepped.iter().sum::<u64>() {
+            println!("Check: day {} for the test fish is {} as expected", days, total);
+        } else {
+            println!("Check FAILED: day {} for the test fish is {}, expected {}", days, total, expected);
+        }
+    }
+    // the matrix and stepping must agree for other life cycles too, e.g. with no newborn delay.
+    for &(cycle, newborn_delay) in MODEL_CHECKS.iter() {
+        let model = LifeCycle {cycle, newborn_delay, max_age: None};
+        let fish = vec![1; model.timers()];
+        let days: u64 = 40;
+        let stepped = count_stepped(&fish, days, &model).expect("The check fish overflowed");
+        let total = count_big(&fish, days, &model);
+        if total != BigUint::from_u64(stepped) {
+            println!("Check FAILED: cycle {}, delay {}: the matrix gives {} but stepping gives {}",
+                     cycle, newborn_delay, total, stepped);
+        }
+    }
+}
+
+
+fn run(options: &[String], default_days: u64) {
+    let days = utils::option_value::<u64>(options, "--days")
+        .expect("Bad --days option")
+        .unwrap_or(default_days);
+    let modulus = utils::option_value::<u64>(options, "--modulo").expect("Bad --modulo option");
+    let csv_file = utils::option_value::<String>(options, "--csv").expect("Bad --csv option");
+    let model = LifeCycle::from_options(options).expect("Bad life cycle options");
+    check_regressions();
+    let file_name = utils::input_file(options, "./input/day06.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name)
         .expect("Error reading the file");
     if lines.len() != 1 {
         println!("Input file has either no lines or more than 1: {}", lines.len());
         return;
     }
-    let mut fish = parse_fish(&lines[0]).expect("Failed to parse fish");
-    println!("Fish are: {:?}", fish);
-    for i in 1..=256 {
-        fish = age_fish(&fish);
-        if i == 18  || i == 80 || i == 256{
-            println!("Day {}, fish = {:?}, total = {}", i, fish, fish.iter().sum::<u64>());
+    let fish = parse_fish(&lines[0], &model).expect("Failed to parse fish");
+    println!("Fish are: {:?}, life cycle: {:?}", fish, model);
+    match (model.max_age, modulus) {
+        (_, Some(0)) => println!("The modulus can't be 0"),
+        (Some(_), Some(_)) => println!("--modulo can't be used with --max-age"),
+        (Some(_), _) => match count_stepped(&fish, days, &model) {
+            Ok(total) => println!("Day {}, total = {}", days, total),
+            Err(e) => println!("{}", e),
+        },
+        (None, Some(m)) => println!("Day {}, total = {}", days, count_modular(&fish, days, &model, m)),
+        (None, None) => println!("Day {}, total = {}", days, count_big(&fish, days, &model)),
+    }
+    if let Some(csv_file) = csv_file {
+        if days > MAX_STEPPED_DAYS {
+            println!("Couldn't make the time series: it can't go past {} days", MAX_STEPPED_DAYS);
+            return;
+        }
+        let mut out = BufWriter::new(File::create(&csv_file).expect("Couldn't create the csv file"));
+        match write_time_series(&fish, days, &model, &mut out) {
+            Ok(()) => println!("Wrote the time series to {}", csv_file),
+            Err(e) => println!("Couldn't make the time series: {}", e),
         }
     }
 }
+
+
+pub fn day6_1(options: &[String]) {
+    println!("Day 6: Lanternfish");
+    run(options, 80);
+}
+
+
+pub fn day6_2(options: &[String]) {
+    println!("Day 6: Lanternfish");
+    run(options, 256);
+}
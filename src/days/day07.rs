@@ -39,76 +39,437 @@ use std::num::ParseIntError;
 use crate::utils;
 
 
-fn parse_line<S: AsRef<str>>(line: S) -> Result<Vec<u32>, ParseIntError>  {
-    line.as_ref().split(",")
-        .map(|l| l.parse::<u32>())
+fn parse_line<S: AsRef<str>>(line: S) -> Result<Vec<u64>, ParseIntError>  {
+    line.as_ref().split(',')
+        .map(|l| l.trim().parse::<u64>())
         .collect::<Result<Vec<_>, _>>()
 }
 
-fn find_min_fuel(positions: &Vec<u32>, part2: bool) -> u32 {
-    let mut max_pos: u32 = 0;
-    let mut min_pos: u32 = u32::MAX;
-    for &p in positions.iter() {
-        if p > max_pos {
-            max_pos = p;
+
+/// How much fuel a crab uses to move a distance, None if it overflows.  If the total over all
+/// the crabs is convex in the position then a ternary search will find the minimum;
+/// 'candidates' lets a cost model that knows where the minimum is say so directly.
+trait FuelCost {
+    fn cost(&self, distance: u64) -> Option<u64>;
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+
+    // positions that are sure to include an optimum, given the sorted positions.
+    fn candidates(&self, _sorted: &[u64]) -> Option<Vec<u64>> {
+        None
+    }
+
+    // the total for all the crabs at a position worked out from prefix sums, if the model can.
+    fn total(&self, _sums: &PrefixSums, _pos: u64) -> Option<u64> {
+        None
+    }
+}
+
+
+// each step costs 1 fuel; the median is optimal.
+struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: u64) -> Option<u64> {
+        Some(distance)
+    }
+
+    fn candidates(&self, sorted: &[u64]) -> Option<Vec<u64>> {
+        Some(vec![sorted[sorted.len() / 2]])
+    }
+
+    fn total(&self, sums: &PrefixSums, pos: u64) -> Option<u64> {
+        sums.polynomial(pos, (0, 1, 1))
+    }
+}
+
+
+// 1 + 2 + 3 ...  = n(n + 1)/2; the optimum is within 1/2 of the mean.
+struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: u64) -> Option<u64> {
+        // halve whichever of n and n + 1 is even first
+        let next = distance.checked_add(1)?;
+        if distance.is_multiple_of(2) {
+            (distance / 2).checked_mul(next)
+        } else {
+            distance.checked_mul(next / 2)
         }
-        if p < min_pos {
-            min_pos = p;
+    }
+
+    fn candidates(&self, sorted: &[u64]) -> Option<Vec<u64>> {
+        Some(around_mean(sorted))
+    }
+
+    fn total(&self, sums: &PrefixSums, pos: u64) -> Option<u64> {
+        sums.polynomial(pos, (1, 1, 2))
+    }
+}
+
+
+// n^2; the mean is optimal.
+struct Quadratic;
+
+impl FuelCost for Quadratic {
+    fn cost(&self, distance: u64) -> Option<u64> {
+        distance.checked_mul(distance)
+    }
+
+    fn candidates(&self, sorted: &[u64]) -> Option<Vec<u64>> {
+        Some(around_mean(sorted))
+    }
+
+    fn total(&self, sums: &PrefixSums, pos: u64) -> Option<u64> {
+        sums.polynomial(pos, (1, 0, 1))
+    }
+}
+
+
+// each step costs 1 fuel, but no crab uses more than 'cap'.  This isn't convex, but the total
+// is linear between the crabs and the positions 'cap' either side of them, so the optimum is at
+// one of those.
+struct Capped {
+    cap: u64,
+}
+
+impl FuelCost for Capped {
+    fn cost(&self, distance: u64) -> Option<u64> {
+        Some(distance.min(self.cap))
+    }
+
+    fn is_convex(&self) -> bool {
+        false
+    }
+
+    fn candidates(&self, sorted: &[u64]) -> Option<Vec<u64>> {
+        let mut candidates = sorted.iter()
+            .flat_map(|&p| [p.saturating_sub(self.cap), p, p.saturating_add(self.cap)])
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        candidates.dedup();
+        Some(candidates)
+    }
+
+    fn total(&self, sums: &PrefixSums, pos: u64) -> Option<u64> {
+        sums.capped(pos, self.cap)
+    }
+}
+
+
+// the integer positions either side of the mean, with one spare each way.
+fn around_mean(sorted: &[u64]) -> Vec<u64> {
+    let sum: u128 = sorted.iter().map(|&p| p as u128).sum();
+    let mean = (sum / sorted.len() as u128) as u64;
+    (mean.saturating_sub(1)..=mean + 2).collect()
+}
+
+
+// e.g. "linear", "triangular", "quadratic" or "capped:100"
+fn cost_model(name: &str) -> Result<Box<dyn FuelCost>, String> {
+    match name.split_once(':') {
+        None if name == "linear" => Ok(Box::new(Linear)),
+        None if name == "triangular" => Ok(Box::new(Triangular)),
+        None if name == "quadratic" => Ok(Box::new(Quadratic)),
+        Some(("capped", cap)) => cap.parse::<u64>()
+            .map(|cap| Box::new(Capped {cap}) as Box<dyn FuelCost>)
+            .map_err(|e| format!("Bad cap '{}': {}", cap, e)),
+        _ => Err(format!("Unknown cost model '{}': use linear, triangular, quadratic or capped:N", name)),
+    }
+}
+
+
+/// The total fuel for all the crabs to move to 'pos'; None if it overflows.
+fn total_fuel(positions: &[u64], pos: u64, cost: &dyn FuelCost) -> Option<u64> {
+    positions.iter()
+        .try_fold(0u64, |total, &p| total.checked_add(cost.cost(p.abs_diff(pos))?))
+}
+
+
+/// Prefix sums of the sorted positions and their squares, to total the cost for any position in
+/// O(log n) rather than going through every crab.
+struct PrefixSums {
+    sorted: Vec<u64>,
+    sums: Vec<u128>,
+    squares: Vec<u128>,
+}
+
+
+impl PrefixSums {
+
+    // None if the sums overflow.
+    fn new(positions: &[u64]) -> Option<Self> {
+        let mut sorted = positions.to_vec();
+        sorted.sort_unstable();
+        let (mut sums, mut squares) = (vec![0u128], vec![0u128]);
+        for &k in sorted.iter() {
+            let k = k as u128;
+            sums.push(sums.last()?.checked_add(k)?);
+            squares.push(squares.last()?.checked_add(k * k)?);
         }
+        Some(Self {sorted, sums, squares})
     }
-    let mut min_fuel = u32::MAX;
-    for p in min_pos..=max_pos {
-        let fuel_used = calc_offsets(&positions, p, part2);
-        if fuel_used < min_fuel {
-            println!("Found a new minimum: {} at {}", fuel_used, p);
-            min_fuel = fuel_used;
+
+    // the total of (a d^2 + b d) / divisor over the crabs, where d is the distance to 'pos'.  The
+    // crabs at or left of pos have sum(d) = c pos - s and sum(d^2) = c pos^2 - 2 pos s + q, and
+    // those to the right the same with the signs flipped.
+    fn polynomial(&self, pos: u64, (a, b, divisor): (u128, u128, u128)) -> Option<u64> {
+        let n = self.sorted.len();
+        let i = self.sorted.partition_point(|&k| k <= pos);
+        let p = pos as u128;
+        let (c_left, s_left, q_left) = (i as u128, self.sums[i], self.squares[i]);
+        let (c_right, s_right, q_right) = ((n - i) as u128, self.sums[n] - s_left, self.squares[n] - q_left);
+        let p_squared = p.checked_mul(p)?;
+        let d = (c_left * p - s_left) + (s_right - c_right * p);
+        let d_left = c_left.checked_mul(p_squared)?.checked_add(q_left)?.checked_sub(p.checked_mul(2 * s_left)?)?;
+        let d_right = c_right.checked_mul(p_squared)?.checked_add(q_right)?.checked_sub(p.checked_mul(2 * s_right)?)?;
+        let total = a.checked_mul(d_left.checked_add(d_right)?)?.checked_add(b.checked_mul(d)?)?;
+        u64::try_from(total / divisor).ok()
+    }
+
+    // the total of min(d, cap) over the crabs: those closer than 'cap' to pos add d (as above) and
+    // the rest add cap.
+    fn capped(&self, pos: u64, cap: u64) -> Option<u64> {
+        if cap == 0 {
+            return Some(0);
         }
+        let n = self.sorted.len();
+        let l = pos.checked_sub(cap).map_or(0, |edge| self.sorted.partition_point(|&k| k <= edge));
+        let m = self.sorted.partition_point(|&k| k <= pos);
+        let r = pos.checked_add(cap).map_or(n, |edge| self.sorted.partition_point(|&k| k < edge));
+        let p = pos as u128;
+        let near = ((m - l) as u128 * p - (self.sums[m] - self.sums[l])) + ((self.sums[r] - self.sums[m]) - (r - m) as u128 * p);
+        let far = (n - (r - l)) as u128 * cap as u128;
+        u64::try_from(near.checked_add(far)?).ok()
     }
-    min_fuel
 }
 
 
-/// calc_offsets; part2 switches on the increased cost of moving for part2
-fn calc_offsets(positions: &Vec<u32>, pos: u32, part2: bool) -> u32 {
-    let mut total: u32 = 0;
-    for &p in positions.iter() {
-        let distance = if p >= pos {
-            p - pos
-        } else {
-            pos - p
-        };
-        if !part2 {
-            total += distance;
+/// The total fuel for any position: from the prefix sums if the cost model can use them, else
+/// (or if they overflow where adding up each crab doesn't) by going through every crab.
+struct FuelTotals<'a> {
+    positions: &'a [u64],
+    cost: &'a dyn FuelCost,
+    sums: Option<PrefixSums>,
+}
+
+
+impl<'a> FuelTotals<'a> {
+
+    fn new(positions: &'a [u64], cost: &'a dyn FuelCost) -> Self {
+        Self {positions, cost, sums: PrefixSums::new(positions)}
+    }
+
+    fn at(&self, pos: u64) -> Option<u64> {
+        self.sums.as_ref()
+            .and_then(|sums| self.cost.total(sums, pos))
+            .or_else(|| total_fuel(self.positions, pos, self.cost))
+    }
+}
+
+
+// integer ternary search for the minimum of a convex function on [lo, hi].  With a tie the
+// minimum is between the two probes (inclusive), so the range still shrinks.
+fn ternary_search<F>(mut lo: u64, mut hi: u64, f: F) -> u64
+    where F: Fn(u64) -> u64
+{
+    while hi - lo > 2 {
+        let m1 = lo + (hi - lo) / 3;
+        let m2 = hi - (hi - lo) / 3;
+        let (f1, f2) = (f(m1), f(m2));
+        if f1 < f2 {
+            hi = m2 - 1;
+        } else if f1 > f2 {
+            lo = m1 + 1;
         } else {
-            // 1 + 2 + 3 ...  = n(n + 1)/2
-            total += (distance * (distance + 1)) / 2;
+            lo = m1;
+            hi = m2;
         }
     }
-    total
+    (lo..=hi).min_by_key(|&p| f(p)).unwrap()
 }
 
-pub fn day7_1() {
-    println!("Day 7-1: The Treachery of Whales");
-    let lines = utils::read_file_single_result::<String>("./input/day07.txt")
-        .expect("Error reading the file");
-    if lines.len() != 1 {
-        println!("Input file has either no lines or more than 1: {}", lines.len());
-        return;
+
+/// Find the position with the least total fuel, and that fuel.  Uses the cost model's own
+/// candidates if it has them, else a ternary search if it's convex, else tries every position.
+fn find_min_fuel(positions: &[u64], cost: &dyn FuelCost) -> Result<(u64, u64), String> {
+    if positions.is_empty() {
+        return Err("There are no crabs".to_string());
     }
-    let positions = parse_line(&lines[0]).expect("Error parsing ints");
-    let min_fuel = find_min_fuel(&positions, false);
-    println!("Min fuel for {:?} is {}", positions, min_fuel);
+    let mut sorted = positions.to_vec();
+    sorted.sort_unstable();
+    let (lo, hi) = (sorted[0], sorted[sorted.len() - 1]);
+    let totals = FuelTotals::new(&sorted, cost);
+    let fuel = |p: u64| totals.at(p).unwrap_or(u64::MAX);
+    let best = match cost.candidates(&sorted) {
+        Some(candidates) => candidates.into_iter()
+            .filter(|p| (lo..=hi).contains(p))
+            .min_by_key(|&p| fuel(p))
+            .unwrap_or(lo),
+        None if cost.is_convex() => ternary_search(lo, hi, fuel),
+        None => (lo..=hi).min_by_key(|&p| fuel(p)).unwrap(),
+    };
+    totals.at(best)
+        .map(|f| (best, f))
+        .ok_or_else(|| format!("The total fuel overflowed at position {}", best))
 }
 
-pub fn day7_2() {
-    println!("Day 7-2: The Treachery of Whales - exponential fuel");
-    let lines = utils::read_file_single_result::<String>("./input/day07.txt")
+
+// The fuel at the optimum, the other positions that tie with it, what each crab
+// uses to get there, and the total fuel for the positions between the outer crabs: every one of
+// them, or MAX_CURVE_POINTS evenly spread (and the optimum) if there are more than that.
+#[derive(Debug)]
+struct Report {
+    optimum: u64,
+    fuel: u64,
+    ties: Vec<u64>,
+    per_crab: Vec<(u64, u64)>,
+    curve: Vec<(u64, u64)>,
+}
+
+
+const MAX_CURVE_POINTS: u64 = 10_000;
+
+
+fn curve_positions(lo: u64, hi: u64, optimum: u64) -> Vec<u64> {
+    if hi - lo < MAX_CURVE_POINTS {
+        return (lo..=hi).collect();
+    }
+    let step = (hi - lo).div_ceil(MAX_CURVE_POINTS - 1);
+    let mut curve = (lo..=hi).step_by(step as usize).collect::<Vec<_>>();
+    curve.push(hi);
+    curve.push(optimum);
+    curve.sort_unstable();
+    curve.dedup();
+    curve
+}
+
+
+// the other positions with the same fuel as the optimum.  If the cost is convex they're next to
+// it, so walk out each way until the fuel goes up; otherwise they could be anywhere.
+fn find_ties<F>(lo: u64, hi: u64, optimum: u64, fuel: u64, convex: bool, fuel_at: F) -> Vec<u64>
+    where F: Fn(u64) -> Option<u64>
+{
+    if !convex {
+        return (lo..=hi).filter(|&p| p != optimum && fuel_at(p) == Some(fuel)).collect();
+    }
+    let mut ties = (lo..optimum).rev().take_while(|&p| fuel_at(p) == Some(fuel)).collect::<Vec<_>>();
+    ties.reverse();
+    ties.extend((optimum + 1..=hi).take_while(|&p| fuel_at(p) == Some(fuel)));
+    ties
+}
+
+
+fn make_report(positions: &[u64], cost: &dyn FuelCost) -> Result<Report, String> {
+    let (optimum, fuel) = find_min_fuel(positions, cost)?;
+    let lo = *positions.iter().min().unwrap();
+    let hi = *positions.iter().max().unwrap();
+    let totals = FuelTotals::new(positions, cost);
+    let fuel_at = |p: u64| totals.at(p);
+    let curve = curve_positions(lo, hi, optimum).into_iter()
+        .map(|p| fuel_at(p)
+             .map(|f| (p, f))
+             .ok_or_else(|| format!("The total fuel overflowed at position {}", p)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let ties = find_ties(lo, hi, optimum, fuel, cost.is_convex(), fuel_at);
+    let per_crab = positions.iter()
+        .map(|&p| cost.cost(p.abs_diff(optimum))
+             .map(|f| (p, f))
+             .ok_or_else(|| format!("The fuel overflowed for the crab at {}", p)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Report {optimum, fuel, ties, per_crab, curve})
+}
+
+
+fn curve_csv(report: &Report) -> String {
+    let mut csv = String::from("position,fuel\n");
+    for (p, f) in report.curve.iter() {
+        csv.push_str(&format!("{},{}\n", p, f));
+    }
+    csv
+}
+
+
+// a sideways bar chart of the curve, sampled down to at most 'rows' rows; '*' marks the row with
+// the optimum.
+fn draw_chart(report: &Report, rows: usize, width: usize) {
+    let max_fuel = report.curve.iter().map(|&(_, f)| f).max().unwrap_or(0).max(1);
+    let step = report.curve.len().div_ceil(rows.max(1));
+    for chunk in report.curve.chunks(step) {
+        let &(p, f) = chunk.iter().min_by_key(|&&(_, f)| f).unwrap();
+        let bar = (f as u128 * width as u128 / max_fuel as u128) as usize;
+        let mark = if chunk.iter().any(|&(q, _)| q == report.optimum) { '*' } else { ' ' };
+        println!("{:>6} {} {:<width$} {}", p, mark, "#".repeat(bar), f, width = width);
+    }
+}
+
+
+fn print_report(report: &Report) {
+    println!("Optimum is position {} using {} fuel", report.optimum, report.fuel);
+    if report.ties.is_empty() {
+        println!("No other positions tie with it");
+    } else {
+        println!("Tied with positions: {:?}", report.ties);
+    }
+    println!("Fuel for each crab (position: fuel):");
+    for (p, f) in report.per_crab.iter() {
+        println!("  {:>6}: {}", p, f);
+    }
+}
+
+
+fn run(options: &[String], default_cost: &str) {
+    let file_name = utils::input_file(options, "./input/day07.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name)
         .expect("Error reading the file");
     if lines.len() != 1 {
         println!("Input file has either no lines or more than 1: {}", lines.len());
         return;
     }
     let positions = parse_line(&lines[0]).expect("Error parsing ints");
-    let min_fuel = find_min_fuel(&positions, true);
-    println!("Min fuel for {:?} is {}", positions, min_fuel);
+    let name = utils::option_value::<String>(options, "--cost")
+        .expect("Bad --cost option")
+        .unwrap_or_else(|| default_cost.to_string());
+    let cost = cost_model(&name).expect("Bad cost model");
+    let csv_file = utils::option_value::<String>(options, "--csv").expect("Bad --csv option");
+    let chart = utils::has_option(options, "--chart");
+    if !utils::has_option(options, "--report") && csv_file.is_none() && !chart {
+        match find_min_fuel(&positions, cost.as_ref()) {
+            Ok((pos, fuel)) => println!("Min fuel for {} crabs ({} cost) is {} at {}", positions.len(), name, fuel, pos),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
+    let report = match make_report(&positions, cost.as_ref()) {
+        Ok(report) => report,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        },
+    };
+    if utils::has_option(options, "--report") {
+        print_report(&report);
+    }
+    if chart {
+        let rows = utils::option_value::<usize>(options, "--rows").expect("Bad --rows option").unwrap_or(40);
+        draw_chart(&report, rows, 60);
+    }
+    if let Some(csv_file) = csv_file {
+        std::fs::write(&csv_file, curve_csv(&report)).expect("Couldn't write the csv file");
+        println!("Wrote the cost curve to {}", csv_file);
+    }
+    println!("Min fuel for {} crabs ({} cost) is {} at {}", positions.len(), name, report.fuel, report.optimum);
+}
+
+
+pub fn day7_1(options: &[String]) {
+    println!("Day 7-1: The Treachery of Whales");
+    run(options, "linear");
+}
+
+pub fn day7_2(options: &[String]) {
+    println!("Day 7-2: The Treachery of Whales - exponential fuel");
+    run(options, "triangular");
 }
@@ -3,17 +3,15 @@
 //You barely reach the safety of the cave when the whale smashes into the cave mouth, collapsing it. Sensors indicate another exit to this cave at a much greater depth, so you have no choice but to press on.
 
 use std::str::FromStr;
-use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
-use std::cell::RefCell;
 
 use thiserror::Error;
 
 use crate::utils;
 
-// might not need this
-const LEDS: &'static [&str] = &[
+// the segments lit for each digit on a normal seven segment display; the default symbol table.
+const LEDS: &[&str] = &[
     "abcefg",  // 0
     "cf",      // 1
     "acdeg",   // 2
@@ -32,13 +30,21 @@ pub enum DecodeError {
     InvalidError(String),
     #[error("Sequence not found?")]
     NotFound(String),
-    #[error("Oddity happened?")]
-    OddityError(String),
+    #[error("No wiring fits: {0}")]
+    NoMapping(String),
+    #[error("More than one wiring fits: {0}")]
+    Ambiguous(String),
 }
 
 
+// The segments are bits in a Segments, a = bit 0.  A u16 covers displays of up to 16 segments
+// (a to p); a u8 would do for 8.
+type Segments = u16;
+const MAX_SEGMENTS: usize = Segments::BITS as usize;
+
+
 #[derive(Clone, Hash, PartialEq, Eq)]
-struct Bits(u8, usize);
+struct Bits(Segments, usize);
 
 
 #[derive(Clone)]
@@ -67,77 +73,38 @@ impl fmt::Display for Bits {
 
 
 impl Bits {
-    fn new(v: u8) -> Self {
-        Self(v, utils::count_bits(v as usize, 7))
+    fn new(v: Segments) -> Self {
+        Self(v, utils::count_bits(v as usize, MAX_SEGMENTS))
     }
 
-    fn bits(&self) -> u8 {
+    fn bits(&self) -> Segments {
         self.0
     }
 
-    fn u8_from_char(v: char) -> Result<u8, DecodeError> {
+    fn bit_from_char(v: char) -> Result<Segments, DecodeError> {
         match v {
-            'a' => Ok(0b00000001),
-            'b' => Ok(0b00000010),
-            'c' => Ok(0b00000100),
-            'd' => Ok(0b00001000),
-            'e' => Ok(0b00010000),
-            'f' => Ok(0b00100000),
-            'g' => Ok(0b01000000),
+            'a'..='z' if ((v as u8 - b'a') as usize) < MAX_SEGMENTS => Ok(1 << (v as u8 - b'a')),
             _ => Err(DecodeError::InvalidError(format!("Got '{}' for an led segment?", v))),
         }
     }
 
+    // the number of segments needed to show these bits; always at least 7.
+    fn width(&self) -> usize {
+        (Segments::BITS - self.0.leading_zeros()).max(7) as usize
+    }
+
     fn to_str(&self) -> String {
-        let mut cs: Vec<char> = Vec::with_capacity(7);
-        let mut bit: u8 = 1;
-        for c in "abcdefg".chars() {
-            if self.0 & bit == bit {
-                cs.push(c);
-            }
-            bit *= 2;
-        }
-        cs.iter().collect()
+        (0..MAX_SEGMENTS).filter(|&b| self.0 & (1 << b) != 0).map(segment_char).collect()
     }
 
     fn to_bit_str(&self) -> String {
-        let mut cs: Vec<char> = Vec::with_capacity(7);
-        let mut bit: u8 = 1;
-        for _ in 0..7 {
-            if self.0 & bit == bit {
-                cs.push('1');
-            } else {
-                cs.push('0');
-            }
-            bit *= 2;
-        }
-        cs.reverse();
-        cs.iter().collect()
+        (0..self.width()).rev().map(|b| if self.0 & (1 << b) != 0 { '1' } else { '0' }).collect()
     }
 
     fn len(&self) -> usize {
         self.1
     }
 
-    fn is_subset(&self, other: &Self) -> bool {
-        other.0 & self.0 == self.0
-    }
-
-    // bits that are in self but not in other
-    fn difference(&self, other: &Self) -> u8 {
-        self.0 ^ (self.0 & other.0)
-    }
-
-    // bits that are in both
-    fn intersection(&self, other: &Self) -> Bits {
-        Bits::new(self.0 & other.0)
-    }
-
-    // combine bits in both
-    fn union(&self, other: &Self) -> Bits {
-        Bits::new(self.0 | other.0)
-    }
-
 }
 
 
@@ -145,10 +112,9 @@ impl FromStr for Bits {
     type Err = DecodeError;
 
     fn from_str(sequence: &str) -> Result<Self, Self::Err> {
-        let mut led: u8 = 0;
+        let mut led: Segments = 0;
         for segment in sequence.chars() {
-            let bit: u8 = Self::u8_from_char(segment)?;
-            led |= bit;
+            led |= Self::bit_from_char(segment)?;
         }
         Ok(Self::new(led))
     }
@@ -186,10 +152,6 @@ impl Led {
         format!("{:7}-({:7})", self.0, self.1.to_str())
     }
 
-    fn str_part(&self) -> String {
-        self.0.clone()
-    }
-
     fn to_bit_str(&self) -> String {
         self.1.to_bit_str()
     }
@@ -231,351 +193,381 @@ impl FromStr for Line {
 
 impl Line {
 
-    /// find a code by a substring and size.  First it filters by size, and then by substring
-    /// Note that it only returns a single item, or None.
-    fn find_one_code_by_bits_and_len(&self, bits: &Bits, size: usize) -> Result<String, DecodeError>
-    {
-        let cs = self.codes.iter()
-            .filter(|led| led.len() == size && bits.is_subset(&led.bits()))
-            .collect::<Vec<_>>();
+    // every distinct pattern on the line, the ten codes and the output digits.
+    fn patterns(&self) -> Vec<Bits> {
+        let mut patterns: Vec<Bits> = Vec::new();
+        for led in self.codes.iter().chain(self.digits.iter()) {
+            if !patterns.contains(&led.1) {
+                patterns.push(led.bits());
+            }
+        }
+        patterns
+    }
+}
 
-        if cs.len() != 1 {
-            return Err(DecodeError::OddityError(format!("No codes with substr {} and size {}?", &bits, &size)));
+
+/// The symbols a display can show, e.g. the digits 0 to 9, and the segments that each lights.
+/// They're either the puzzle's digits or loaded from a definition file of lines like "4 bcdf".
+#[derive(Debug, Clone)]
+struct SymbolTable {
+    names: Vec<String>,
+    patterns: Vec<Bits>,
+    segments: usize,
+}
+
+
+impl SymbolTable {
+
+    fn new(entries: Vec<(String, Bits)>) -> Result<Self, DecodeError> {
+        if entries.len() < 2 {
+            return Err(DecodeError::InvalidError("A display needs at least two symbols".to_string()));
         }
-        Ok(cs.get(0).unwrap().str_part())
+        for (i, (name, bits)) in entries.iter().enumerate() {
+            if let Some((other, _)) = entries[..i].iter().find(|(n, b)| n == name || b == bits) {
+                return Err(DecodeError::InvalidError(format!("Symbols '{}' and '{}' clash", other, name)));
+            }
+        }
+        let segments = entries.iter()
+            .map(|(_, b)| (Segments::BITS - b.bits().leading_zeros()) as usize)
+            .max()
+            .unwrap_or(0);
+        let (names, patterns) = entries.into_iter().unzip();
+        Ok(Self {names, patterns, segments})
+    }
+
+    fn digits() -> Self {
+        let entries = LEDS.iter()
+            .enumerate()
+            .map(|(i, l)| (i.to_string(), l.parse::<Bits>().unwrap()))
+            .collect::<Vec<_>>();
+        Self::new(entries).unwrap()
     }
 
-    // find the first sequence of length passed;
-    // TODO: need to switch it to return the Led, and not the string of the Led
-    fn find_first_code_len(&self, length: usize) -> Result<String, DecodeError> {
-        for code in self.codes.iter() {
-            if code.len() == length {
-                return Ok(code.0.clone());
+    fn load(file_name: &str) -> Result<Self, DecodeError> {
+        let lines = utils::read_file_single_result::<String>(file_name)
+            .map_err(|e| DecodeError::InvalidError(format!("Couldn't read {}: {:?}", file_name, e)))?;
+        let mut entries: Vec<(String, Bits)> = Vec::new();
+        for line in lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [name, pattern] => entries.push((name.to_string(), pattern.parse::<Bits>()?)),
+                _ => return Err(DecodeError::InvalidError(format!("Expected 'name segments': {}", line))),
             }
         }
-        Err(DecodeError::NotFound(format!("Couldn't find sequence of length {}", length)))
+        Self::new(entries)
+    }
+
+    fn from_options(options: &[String]) -> Result<Self, DecodeError> {
+        match utils::option_value::<String>(options, "--symbols").map_err(DecodeError::InvalidError)? {
+            Some(file_name) => Self::load(&file_name),
+            None => Ok(Self::digits()),
+        }
+    }
+
+    fn find(&self, bits: &Bits) -> Option<usize> {
+        self.patterns.iter().position(|p| p == bits)
+    }
+
+    /// the symbols that are the only ones with their number of segments.
+    fn unique_by_count(&self) -> Vec<usize> {
+        (0..self.patterns.len())
+            .filter(|&i| self.patterns.iter().filter(|p| p.len() == self.patterns[i].len()).count() == 1)
+            .collect()
+    }
+
+    // a summary of which symbols can be told apart by their segment counts alone.
+    fn describe(&self) -> String {
+        let unique = self.unique_by_count();
+        let names = unique.iter().map(|&i| self.names[i].as_str()).collect::<Vec<_>>().join(", ");
+        if unique.len() == self.names.len() {
+            format!("All {} symbols can be decoded by their segment counts alone", unique.len())
+        } else {
+            format!("{} of {} symbols have unique segment counts ({}); the rest need the solver",
+                    unique.len(), self.names.len(), names)
+        }
+    }
+
+    // the symbols read as a number in base (number of symbols); None if it overflows.
+    fn value(&self, symbols: &[usize]) -> Option<u64> {
+        symbols.iter()
+            .try_fold(0u64, |v, &s| v.checked_mul(self.names.len() as u64)?.checked_add(s as u64))
     }
 }
 
-// -------------------------------------------------------------------------------------------------
 
-// count the 1,4, 7 and 8 digits.
-// part 1 - function
-fn count_unique_digits(lines: &[Line]) -> u32 {
+fn count_unique_digits(lines: &[Line], table: &SymbolTable) -> u32 {
+    let counts = table.unique_by_count().iter().map(|&i| table.patterns[i].len()).collect::<Vec<_>>();
     lines.iter()
-        .map(|l| l.digits.iter().filter(|&d| {
-                let ll = d.len();
-                //    1          4          7          8
-                ll == 2 || ll == 4 || ll == 3 || ll == 7
-            }).count() as u32)
-    .sum()
+        .map(|l| l.digits.iter().filter(|&d| counts.contains(&d.len())).count() as u32)
+        .sum()
 }
 
 
 // -------------------------------------------------------------------------------------------------
 
 // part 2
-// decode the single wires and digits.
-// take the first line of the test data:
+// decode the single wires and digits.  Rather than deducing the digits in a fixed order (1, 7, 4
+// and 8 by their lengths, then the rest), the solver narrows down what each wire and pattern
+// could be until nothing changes and then searches what's left, so it works for any symbol table.
 //
-// be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
-//
-// Digit 1: 'be' drives 'cf' as either 'b->c|f', and 'e->c|f'
-// And 'cgeb' drives 'bcdf' as 'c->b|c|d|f', 'g->'b|c|d|f|, 'e->b|c|d|f'
-// But 'e' also drives 'e->f|c' (from be).
-// So we now 'know', 'e->f|c'
-// Also fom digit 1, 'b->c|f', so that knocks out 'cf' from 'cgeb->bcdf'.
-// This leaves 'gb->bd' (or 'g->bd' and 'b->bd')
-//
-//        be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
-// sorted be abcdefg bcdefg acdefg bceg cdefg abdefg bcdef abcdf bde
-// length 2  7       6      6      4    5     6      5     5     3
-// digit  1  8       9      6      4    5     0      3     2     7
+// e.g. for the first line of the test data:
 //
-// for above line (real -> scrambled):
-// '1' -> cf -> be
-// '7' -> a(cf) -> d(be), leaving a->d **
-// '4' -> bcdf -> bceg, cf(bd) -> be(cg), thus: bd->cg
-// '3' -> acdfg => acf(dg) -> bde(??) [bde(cf), so dg -> cf]
-//  as bd->cg and dg->cf, then d->c AND b->g AND g->f **
-//  mapping so far (abdg -> dgcf)
-// '5' -> abdfg => abdg(f) -> dgcf(e) so, f->e **
-//  mapping so far (abdfg -> dgcef)
-// '2' -> acdeg => adg(ce) -> dcf(ab), so ce -> ab
-//  and cf -> be (from '7'), hence c->b (and f->e also fom '5')
-//
-// '0' -> abcefg => abc(e)fg -> dgb(?)ef  e -> a **
-// '6' -> abdefg => abd(e)fg -> dgc(?)ef, so e->a
-// '9' -> abcdfg => abdcfg -> dgcbef
-//
-// final mapping (abcdefg -> dgbcaef)
+// be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
 //
-// Order of digits 1, 7, 4, 8 (based on lengths).
-// Then 3,5,2,9, (and thus 0 and 6)
+// 'be' can only be 1, so b and e must drive c and f, which takes c and f away from every other
+// wire; 'edb' can only be 7, so d drives a; and so on until the only mapping left has segments
+// abcdefg driven by wires dgbcaef.
 
 
-// Interior mutability as it's actually easier to just stop fighting
-// the borrow checking when using iterators and lifetimes of the things.
-// I do have to clone, though.  Probably could've got away without the
-// interior mutability.
-#[derive(Debug, Clone)]
+/// Which segment each scrambled wire really drives; wires[w] is the segment for wire w, both
+/// as bit numbers (a = 0, b = 1, ...).
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Mapping {
-    map: RefCell<HashMap<u8, Bits>>,      // may the bits of a u8 to a Bits (u8 + count of bits)
+    wires: Vec<usize>,
 }
 
 
 impl fmt::Display for Mapping {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Map:\n")?;
-        for (k, v) in self.map.borrow().iter() {
-            write!(f, "  {:?} -> {:?}\n", Bits::new(*k), v)?;
-        }
-        Ok(())
+        let pairs = self.wires.iter()
+            .enumerate()
+            .map(|(w, &s)| format!("{}->{}", segment_char(w), segment_char(s)))
+            .collect::<Vec<_>>();
+        write!(f, "{}", pairs.join(" "))
     }
 }
 
 
 impl Mapping {
-    fn new() -> Self {
-        Self { map: RefCell::new(HashMap::new()) }
-    }
-
-    fn insert<S>(&self, from: S, to: S) -> Result<(), DecodeError>
-        where S: AsRef<str>
-    {
-        let from = from.as_ref().parse::<Bits>()?;
-        let to = to.as_ref().parse::<Bits>()?;
-        if from.len() != to.len() {
-            return Err(DecodeError::OddityError(
-                format!("Mapping.insert - lengths to match: {:?} -> {:?}", from, to)));
-        }
-        self.map.borrow_mut().insert(from.bits(), to);
-        Ok(())
-    }
 
-    fn insert_u8(&self, from: &u8, to: &Bits) -> Result<(), DecodeError> {
-        if utils::count_bits(*from as usize, 7) != to.len() {
-            return Err(DecodeError::OddityError(
-                format!("Mapping.insert - lengths don't match: {:?} -> {:?}", Bits::new(*from), to)));
+    // turn the scrambled wires into the segments they really light up.
+    fn unscramble(&self, bits: &Bits) -> Bits {
+        let mut v: Segments = 0;
+        for (w, &s) in self.wires.iter().enumerate() {
+            if bits.bits() & (1 << w) != 0 {
+                v |= 1 << s;
+            }
         }
-        self.map.borrow_mut().insert(*from, to.clone());
-        Ok(())
+        Bits::new(v)
     }
+}
 
-    fn add_digit<S>(&self, digit: usize, seq: S) -> Result<(), DecodeError>
-        where S: AsRef<str>
-    {
-        if digit > 9 {
-            panic!("Can't handle a digit larger than 9");
-        }
-        let to = seq.as_ref().parse::<Led>()?;
-        //self.digits.borrow_mut().insert(digit, to.clone());                // map the digit to the scrambled sequence
-        let from = LEDS[digit].parse::<Bits>()?;
-        self.map.borrow_mut().insert(from.bits(), to.bits());  // map the real to the scrambled sequence
-        Ok(())
+
+fn segment_char(bit: usize) -> char {
+    (b'a' + bit as u8) as char
+}
+
+
+fn all_segments(segments: usize) -> Segments {
+    ((1u32 << segments) - 1) as Segments
+}
+
+
+/// Works out which wires drive which segments from the patterns seen on a line.  Each wire has a
+/// set of segments it could be; each pattern has the symbols it could be.  Propagation narrows
+/// both until nothing changes, and then a search over what's left finds every consistent
+/// mapping.  As propagation only removes impossible choices, one mapping means it's unique.
+struct Solver<'a> {
+    symbols: &'a [Bits],
+    segments: usize,
+    candidates: Vec<Segments>,
+}
+
+
+impl<'a> Solver<'a> {
+
+    fn new(symbols: &'a [Bits], segments: usize) -> Self {
+        let all = all_segments(segments);
+        Self {symbols, segments, candidates: vec![all; segments]}
     }
 
-    fn map_get<S: AsRef<str>>(&self, seq: S) -> Result<Bits, DecodeError> {
-        let s = seq.as_ref().parse::<Bits>()?;
-        self.map.borrow().get(&s.bits())
-            .ok_or(DecodeError::NotFound(format!("Sequence '{}' not found?", &s)))
-            .map(|v| v.clone())
+    fn all(&self) -> Segments {
+        all_segments(self.segments)
     }
 
-    fn map_get_u8(&self, bits: &u8) -> Result<Bits, DecodeError> {
-        self.map.borrow().get(&bits)
-            .ok_or(DecodeError::NotFound(format!("Sequence '{}' not found?", &bits)))
-            .map(|v| v.clone())
+    // can 'pattern' be 'symbol' given what the wires might be?
+    fn could_be(&self, pattern: &Bits, symbol: &Bits) -> bool {
+        pattern.len() == symbol.len() &&
+            (0..self.segments).all(|w| {
+                let on = if pattern.bits() & (1 << w) != 0 { symbol.bits() } else { !symbol.bits() & self.all() };
+                self.candidates[w] & on != 0
+            })
     }
 
-    fn derive_from<S>(&self, c: char, s1: S, s2: S) -> Result<(), DecodeError>
-        where S: AsRef<str> + fmt::Display,
-    {
-        if s1.as_ref().len() != s1.as_ref().len() {
-            return Err(DecodeError::OddityError(
-                format!("derive_from: strings not same length: '{}' and '{}'", &s1, &s2)));
+    // narrow the candidates until nothing changes; false if some wire or pattern has no options.
+    fn propagate(&mut self, patterns: &[Bits]) -> bool {
+        loop {
+            let before = self.candidates.clone();
+            for pattern in patterns.iter() {
+                let (mut lit, mut unlit): (Segments, Segments) = (0, 0);
+                for symbol in self.symbols.iter().filter(|s| self.could_be(pattern, s)) {
+                    lit |= symbol.bits();
+                    unlit |= !symbol.bits() & self.all();
+                }
+                for w in 0..self.segments {
+                    self.candidates[w] &= if pattern.bits() & (1 << w) != 0 { lit } else { unlit };
+                }
+            }
+            // a wire with one segment left has it to itself, and a segment only one wire can be
+            // must be that wire.
+            for w in 0..self.segments {
+                let c = self.candidates[w];
+                if c.count_ones() == 1 {
+                    for (o, other) in self.candidates.iter_mut().enumerate() {
+                        if o != w {
+                            *other &= !c;
+                        }
+                    }
+                }
+            }
+            for s in 0..self.segments {
+                let wires = (0..self.segments).filter(|&w| self.candidates[w] & (1 << s) != 0).collect::<Vec<_>>();
+                if let [w] = wires[..] {
+                    self.candidates[w] = 1 << s;
+                }
+            }
+            if self.candidates.contains(&0) {
+                return false;
+            }
+            if self.candidates == before {
+                return true;
+            }
         }
-        let bits1 = self.map_get(s1.as_ref())?;
-        let bits2 = self.map_get(s2.as_ref())?;
-        let ds_bits = bits1.intersection(&bits2);
-        if ds_bits.len() != 1 {
-            return Err(DecodeError::OddityError(format!("Expected only a single digit, got {:?}", &ds_bits)));
+    }
+
+    // is every pattern a symbol under the mapping?
+    fn consistent(&self, patterns: &[Bits], mapping: &Mapping) -> bool {
+        patterns.iter().all(|p| self.symbols.contains(&mapping.unscramble(p)))
+    }
+
+    // try every permutation the candidates allow, wire by wire.
+    fn search(&self, patterns: &[Bits], wires: &mut Vec<usize>, used: Segments, found: &mut Vec<Mapping>) {
+        let w = wires.len();
+        if w == self.segments {
+            let mapping = Mapping {wires: wires.clone()};
+            if self.consistent(patterns, &mapping) {
+                found.push(mapping);
+            }
+            return;
         }
-        let c_bits = Bits::from_str(&c.to_string())?;
-        self.insert_u8(&c_bits.bits(), &ds_bits)?;
-        Ok(())
-    }
-
-    /// derive the sequence s using the other sequences that are availably in the mapping.
-    /// Essentially, find all the codes that contain 's', sorted by size, and attempt to remove
-    /// already identified mappings, until 's' is known.
-    fn derive<S>(&self, s: S) -> Result<(), DecodeError>
-        where S: AsRef<str> + fmt::Display,
-    {
-        // get all the mappings that contain 's', if we find s, then just return
-        let bits = s.as_ref().parse::<Bits>()?;
-        //println!("Trying to derive: {:?}", &bits);
-        let mut keys = self.map.borrow().keys()
-            .map(|ks| Bits::new(*ks))         // turn a u8 back into a Bits with a length
-            .filter(|ks| bits.is_subset(ks))
-            .collect::<Vec<_>>();
-        keys.sort_by(|a,b| a.len().cmp(&b.len()));
-        //println!("Found {:?}", &keys);
-        let diffs: Vec<_> = keys.iter().map(|k| self.map_get_u8(&k.difference(&bits))).collect();
-        for (key, diff_res) in keys.iter().zip(diffs.iter()) {
-            match diff_res {
-                Ok(dv) => {
-                    // we matched the remaining part of the key, so we can resolve the difference.
-                    let to = self.map_get_u8(&key.bits())
-                                 .map(|v| v.difference(&dv))?;
-                    //println!("matched key {:?} remainders= {:?} -> {:?}.", &key, &bits, &Bits::new(to));
-                    self.insert_u8(&bits.bits(), &Bits::new(to))?;
-                    return Ok(());
-                },
-                Err(_) => {},
+        for s in 0..self.segments {
+            if self.candidates[w] & !used & (1 << s) != 0 {
+                wires.push(s);
+                self.search(patterns, wires, used | (1 << s), found);
+                wires.pop();
             }
         }
-        Err(DecodeError::OddityError(format!("Couldn't derive {}?", &s)))
     }
 
-    fn combine<S>(&self, s1: S, s2: S) -> Result<(), DecodeError>
-        where S: AsRef<str> + fmt::Display,
-    {
-        let t: String = vec![s1.as_ref(), s2.as_ref()].concat();
-        let t_bits = Bits::from_str(&t)?;
-        // early return if it already exists
-        if let Ok(_) = self.map_get_u8(&t_bits.bits()) {
-            return Ok(());
+    /// every mapping that turns all the patterns into symbols.
+    fn solve(mut self, patterns: &[Bits]) -> Vec<Mapping> {
+        let mut found: Vec<Mapping> = Vec::new();
+        if self.propagate(patterns) {
+            self.search(patterns, &mut Vec::with_capacity(self.segments), 0, &mut found);
         }
-        let bits1 = self.map_get(s1.as_ref())?;
-        let bits2 = self.map_get(s2.as_ref())?;
-        let u_bits = bits1.union(&bits2);
-        self.insert_u8(&t_bits.bits(), &u_bits)?;
-        Ok(())
-    }
-
-    /// Resolve a segment set from the available mappings of single characters
-    fn resolve<S>(&self, r: S) -> Result<(), DecodeError>
-        where S: AsRef<str> + fmt::Display,
-    {
-        let mut s = String::with_capacity(7);
-        let mut r_iter = r.as_ref().chars();
-        match r_iter.next() {
-            Some(c) => s.push(c),
-            None => return Err(DecodeError::OddityError("No string passed to resolve?".to_string())),
-        };
-        while let Some(c) = r_iter.next() {
-            self.combine(c.to_string(), s.clone())?;
-            s.push(c);
-        }
-        Ok(())
+        found
     }
+}
+
 
+/// The one wiring that fits a line; an error if none or several do.
+fn solve_line(line: &Line, table: &SymbolTable) -> Result<Mapping, DecodeError>  {
+    let patterns = line.patterns();
+    if let Some(p) = patterns.iter().find(|p| p.bits() & !all_segments(table.segments) != 0) {
+        return Err(DecodeError::InvalidError(
+            format!("'{}' uses more than the display's {} segments", p.to_str(), table.segments)));
+    }
+    let mut mappings = Solver::new(&table.patterns, table.segments).solve(&patterns);
+    match mappings.len() {
+        1 => Ok(mappings.remove(0)),
+        0 => Err(DecodeError::NoMapping(format!("{:?}", line))),
+        n => {
+            let all = mappings.iter().map(|m| format!("  {}", m)).collect::<Vec<_>>().join("\n");
+            Err(DecodeError::Ambiguous(format!("{} wirings\n{}", n, all)))
+        },
+    }
 }
 
-// Decode a line (such as the one below) into their correspending mappings of 7-segment display,
-// and decode the digits after the '|' into a a int in the form of '8153'
-// be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
-fn decode_line(line: &Line) -> Result<u32, DecodeError>  {
-    let mapping: Mapping = Mapping::new();
-    for (digit, size) in [(1, 2), (4, 4), (7, 3), (8, 7)] {
-        let seq = line.find_first_code_len(size)?;
-        mapping.add_digit(digit, seq)?;
-    }
-    // derive 'a' from digits '7' (acf) and '1' (cf)
-    //mapping.derive_from_digits(1, 7)?;
-    //println!("1 - Mapping now is: {}", &mapping);
-    mapping.derive("a")?;
-    // derive 'bd' from digit '4' -> bcdf, cf(bd) -> be(??), thus: bd->cg
-    //mapping.derive_from_digits(1, 4)?;
-    mapping.derive("bd")?;
-    // '3' -> acdfg => acf(dg) -> bde(??) [bde(cf), so dg -> cf]
-    //  as bd->cg and dg->cf, then d->c AND b->g AND g->f **
-    let acf = mapping.map_get("acf")?;
-    let acdfg = line.find_one_code_by_bits_and_len(&acf, 5)?;
-    // add mapping for acdfg -> ????? above, and then derive 'dg'
-    mapping.insert("acdfg", &acdfg)?;
-    // derive dg by acfdg - acf
-    //mapping.derive_with("acdfg", "acf")?;
-    mapping.derive("dg")?;
-    // now derive 'd' from 'dg' and 'bd'
-    mapping.derive_from('d', "dg", "bd")?;
-    //mapping.derive("d")?;  // Not sure if this is possible?
-    mapping.derive("b")?;
-    mapping.derive("g")?;
-
-//  mapping so far (abdg -> dgcf)
-// '5' -> abdfg => abdg(f) -> dgcf(e) so, f->e **
-    mapping.combine("a", "dg")?;
-    mapping.combine("adg", "b")?;
-    let abdg = mapping.map_get("abdg")?;
-    let abdfg = line.find_one_code_by_bits_and_len(&abdg, 5)?;
-    mapping.insert("abdfg", &abdfg)?;
-    mapping.derive("f")?;
-//  mapping so far (abdfg -> dgcef)
-// '2' -> acdeg => adg(ce) -> dcf(ab), so ce -> ab
-//  and cf -> be (from '7'), hence c->b (and f->e also fom '5')
-//
-// '0' -> abcefg => abc(e)fg -> dgb(?)ef  e -> a **
-// '6' -> abdefg => abd(e)fg -> dgc(?)ef, so e->a
-// '9' -> abcdfg => abdcfg -> dgcbef
-    mapping.derive("c")?;
-    mapping.derive("a")?;
-    mapping.combine("acdfg", "b")?;
-    mapping.derive("e")?;
-//
-    // Now derive the remaining digits.
-    let mut digit_map: HashMap<Bits, usize> = HashMap::with_capacity(10);
-    for (i, led) in LEDS.iter().enumerate() {
-        let l = Led::from_str(led)?;
-        let m = match mapping.map_get_u8(&l.bits().bits()) {
-            Ok(v) => v,
-            Err(_) => {
-                //println!("Resolve {:?}", l);
-                mapping.resolve(l.str_part())?;
-                //println!("Resolved?");
-                mapping.map_get_u8(&l.bits().bits()).unwrap()
-            },
-        };
-        //println!("{} - {:?} - {:?}", i, l, m);
-        digit_map.insert(m, i);
-    }
-
-    // finially, let's work out what the digits are:
-    let digits = line.digits.iter()
-        .map(|d| Bits::from_str(&d.str_part())
-            .and_then(|bits| digit_map.get(&bits)
-                                      .ok_or(DecodeError::OddityError(format!("Couldn't find {:?}", &bits)))))
-        .collect::<Result<Vec<_>, _>>()?;
-    //println!("Line {:?}", &line);
-    //println!("Digits {:?}", &digits);
-    let mut sum: usize = 0;
-    let mut d: usize = 1;
-    for c in digits.iter().rev() {
-        sum += *c * d;
-        d *= 10;
-    }
-    println!("Sum is {}", sum);
-    Ok(sum as u32)
+
+/// The symbols shown by the output digits of a line, as indexes into the table.
+fn decode_line(line: &Line, table: &SymbolTable, mapping: &Mapping) -> Result<Vec<usize>, DecodeError>  {
+    line.digits.iter()
+        .map(|led| {
+            let segments = mapping.unscramble(&led.bits());
+            table.find(&segments)
+                .ok_or_else(|| DecodeError::NotFound(format!("Couldn't find {:?}", &segments)))
+        })
+        .collect()
+}
+
+
+// draw the a to g segments of 'bits' as a 3x3 glyph:
+//   _
+//  |_|
+//  |_|
+fn glyph(bits: &Bits) -> [String; 3] {
+    let on = |c: char, s: char| if bits.bits() & (1 << (s as u8 - b'a')) != 0 { c } else { ' ' };
+    [
+        [' ', on('_', 'a'), ' '].iter().collect(),
+        [on('|', 'b'), on('_', 'd'), on('|', 'c')].iter().collect(),
+        [on('|', 'e'), on('_', 'g'), on('|', 'f')].iter().collect(),
+    ]
+}
+
+
+// the outputs of a line drawn as they are wired, and then unscrambled, with the mapping.
+fn show_line(line: &Line, table: &SymbolTable, mapping: &Mapping, symbols: &[usize]) {
+    let scrambled = line.digits.iter().map(|d| glyph(&d.bits())).collect::<Vec<_>>();
+    let unscrambled = line.digits.iter().map(|d| glyph(&mapping.unscramble(&d.bits()))).collect::<Vec<_>>();
+    for row in 0..3 {
+        let left = scrambled.iter().map(|g| g[row].as_str()).collect::<Vec<_>>().join(" ");
+        let right = unscrambled.iter().map(|g| g[row].as_str()).collect::<Vec<_>>().join(" ");
+        println!("  {}   {}   {}", left, if row == 1 { "->" } else { "  " }, right);
+    }
+    for (led, &s) in line.digits.iter().zip(symbols.iter()) {
+        println!("  {:<8} -> {:<8} = {}", led.1.to_str(), mapping.unscramble(&led.bits()).to_str(), table.names[s]);
+    }
+    let wires = (0..mapping.wires.len()).map(|w| segment_char(w).to_string()).collect::<Vec<_>>();
+    let segments = mapping.wires.iter().map(|&s| segment_char(s).to_string()).collect::<Vec<_>>();
+    println!("  wire    | {}", wires.join(" "));
+    println!("  segment | {}", segments.join(" "));
 }
 
 
-pub fn day8_1() {
+pub fn day8_1(options: &[String]) {
     println!("Day 8: Seven Segment Search");
-    let lines = utils::read_file_single_result::<Line>("./input/day08.txt").expect("Couldn't read file");
+    let table = SymbolTable::from_options(options).expect("Couldn't load the symbols");
+    println!("{}", table.describe());
+    let file_name = utils::input_file(options, "./input/day08.txt");
+    let lines = utils::read_file_single_result::<Line>(&file_name).expect("Couldn't read file");
     println!("Input: {:?}", lines);
-    let s = count_unique_digits(&lines[..]);
-    println!("Sum of unique segment lengths 1,4,7,8: {:?}", s);
+    let s = count_unique_digits(&lines[..], &table);
+    println!("Sum of outputs with unique segment counts: {:?}", s);
 }
 
-pub fn day8_2() {
+pub fn day8_2(options: &[String]) {
     println!("Day 8: Seven Segment Search - part 2: decoding");
-    let lines = utils::read_file_single_result::<Line>("./input/day08.txt").expect("Couldn't read file");
-    println!("Input: {:?}", lines);
-    let mut v: u32 = 0;
-    for line in lines {
-        let d = decode_line(&line).expect("Damn!");
-        v += d;
+    let table = SymbolTable::from_options(options).expect("Couldn't load the symbols");
+    println!("{}", table.describe());
+    let file_name = utils::input_file(options, "./input/day08.txt");
+    let lines = utils::read_file_single_result::<Line>(&file_name).expect("Couldn't read file");
+    let show = utils::has_option(options, "--show");
+    let mut v: u64 = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let decoded = solve_line(line, &table)
+            .and_then(|mapping| decode_line(line, &table, &mapping).map(|symbols| (mapping, symbols)));
+        match decoded {
+            Ok((mapping, symbols)) => {
+                if show {
+                    println!("Line {}:", i + 1);
+                    show_line(line, &table, &mapping, &symbols);
+                }
+                match table.value(&symbols) {
+                    Some(d) => v = v.checked_add(d).expect("The total overflowed"),
+                    None => println!("Line {}: the value overflowed", i + 1),
+                }
+            },
+            Err(e) => println!("Line {}: {}", i + 1, e),
+        }
     }
     println!("Total is {}", v);
 }
@@ -4,59 +4,95 @@
 
 //If you can model how the smoke flows through the caves, you might be able to avoid it and be that much safer. The submarine generates a heightmap of the floor of the nearby caves for you (your puzzle input).
 
-use std::collections::HashSet;
-
 use crate::utils;
 
 #[derive(Debug, Clone)]
 struct Map(Vec<Vec<u32>>);
 
 
-fn parse_lines(lines: &[String]) -> Map {
+fn parse_lines(lines: &[String]) -> Result<Map, String> {
     let r = lines.iter()
-        .map(|line| {
+        .enumerate()
+        .map(|(i, line)| {
             line.chars()
-                .map(|c| c.to_digit(10).unwrap_or(10))
-                .collect::<Vec<_>>()
+                .map(|c| c.to_digit(10).ok_or_else(|| format!("Line {}: '{}' isn't a height", i + 1, c)))
+                .collect::<Result<Vec<_>, _>>()
         })
-        .collect::<Vec<_>>();
-    Map(r)
-}
-
-
-fn neighbours(map: &Map, down: usize, across: usize) -> [u32; 4] {
-    let above = if down == 0 {
-        10
-    } else {
-        map.0[down-1][across]
-    };
-    let below = if down >= map.0.len() -1 {
-        10
-    } else {
-        map.0[down+1][across]
-    };
-    let left = if across == 0 {
-        10
-    } else {
-        map.0[down][across-1]
-    };
-    let right = if across >= map.0[0].len() -1 {
-        10
-    } else {
-        map.0[down][across+1]
-    };
-    [above, right, left, below]  // NEWS in the map
-}
-
-
-/// part 1 - just find the low points.
-fn find_low_points(map: &Map) -> Vec<(usize, usize, u32)> {
+        .collect::<Result<Vec<_>, _>>()?;
+    if r.iter().any(|row| row.len() != r[0].len()) {
+        return Err("The rows aren't all the same length".to_string());
+    }
+    Ok(Map(r))
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Connectivity {
+    Four,
+    Eight,
+}
+
+
+// the offsets of the neighbours, in order going clockwise from above.
+const FOUR: [(i32, i32); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+const EIGHT: [(i32, i32); 8] = [(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1)];
+
+
+/// How the heightmap is read: which cells are next to each other, and which heights are walls
+/// between basins.  The puzzle is 4-connected with walls at 9.
+#[derive(Debug, Clone, Copy)]
+struct Terrain {
+    connectivity: Connectivity,
+    wall_level: u32,
+}
+
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Self {connectivity: Connectivity::Four, wall_level: 9}
+    }
+}
+
+
+impl Terrain {
+
+    fn from_options(options: &[String]) -> Result<Self, String> {
+        let connectivity = if utils::has_option(options, "--diagonals") { Connectivity::Eight } else { Connectivity::Four };
+        let wall_level = utils::option_value::<u32>(options, "--wall")?.unwrap_or(9);
+        Ok(Self {connectivity, wall_level})
+    }
+
+    fn is_wall(&self, height: u32) -> bool {
+        height >= self.wall_level
+    }
+
+    fn offsets(&self) -> &'static [(i32, i32)] {
+        match self.connectivity {
+            Connectivity::Four => &FOUR,
+            Connectivity::Eight => &EIGHT,
+        }
+    }
+
+    // the neighbours that are on the map, clockwise from above; None for those off the edge.
+    fn neighbours(&self, map: &Map, down: usize, across: usize) -> Vec<Option<(usize, usize)>> {
+        self.offsets().iter()
+            .map(|&(dd, da)| {
+                let d = down.checked_add_signed(dd as isize)?;
+                let a = across.checked_add_signed(da as isize)?;
+                if d < map.0.len() && a < map.0[d].len() { Some((d, a)) } else { None }
+            })
+            .collect()
+    }
+}
+
+
+fn find_low_points(map: &Map, terrain: &Terrain) -> Vec<(usize, usize, u32)> {
     let mut points: Vec<(usize, usize, u32)> = vec![];
     for down in 0..map.0.len() {
         for across in 0..map.0[0].len() {
             let here = map.0[down][across];
-            let ns = neighbours(&map, down, across);
-            if ns.iter().all(|h| *h > here) {
+            let ns = terrain.neighbours(map, down, across);
+            if ns.iter().flatten().all(|&(d, a)| map.0[d][a] > here) {
                 points.push((down, across, here));
             }
         }
@@ -65,84 +101,230 @@ fn find_low_points(map: &Map) -> Vec<(usize, usize, u32)> {
     points
 }
 
-// ----
-
-// Part 2 -- find the basin sizes.  e.g. from a low point expand outwards from that point upwards
-// until the 9 is hit. e.g. don't count the 9 in the size.
-//
-// Strategy: stating at the low point; explore the height of the neighbours.  If higher add to a
-// queue to search next.  Each time around, drop one off the queue and search for the next one.
-// Use find_low_points to find the lowest points, and then use neighbours to check the
-// surroundings.
 
 type Point = (usize, usize, u32);  // down, across, height
 
-/// find all the basins, sum them up and return the value.
-fn find_basins(map: &Map) -> u32 {
-    let points = find_low_points(&map);
-    let mut sizes: Vec<u32> = Vec::new();
-    for point in points.iter() {
-        let size = find_basin_size(&map, &point);
-        println!("Size of {:?} is {}", &point, &size);
-        sizes.push(size);
+
+// union-find over the cells of the map, indexed by down * width + across.
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect(), size: vec![1; n] }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+
+#[derive(Debug, Clone)]
+struct Basin {
+    low: Point,                   // the lowest cell; the first one found if there's a tie
+    cells: Vec<(usize, usize)>,   // down, across
+}
+
+
+impl Basin {
+    fn size(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+
+/// Every cell that isn't a wall labelled with its basin; labels index into 'basins'.
+#[derive(Debug, Clone)]
+struct Basins {
+    labels: Vec<Vec<Option<usize>>>,
+    basins: Vec<Basin>,
+}
+
+
+// join each open cell to its open neighbours, and then number the sets in the order they're
+// first seen.
+fn label_basins(map: &Map, terrain: &Terrain) -> Basins {
+    let height = map.0.len();
+    let width = map.0.first().map_or(0, |r| r.len());
+    let open = |d: usize, a: usize| !terrain.is_wall(map.0[d][a]);
+    let mut set = DisjointSet::new(height * width);
+    for down in 0..height {
+        for across in 0..width {
+            if !open(down, across) {
+                continue;
+            }
+            for &(d, a) in terrain.neighbours(map, down, across).iter().flatten() {
+                if open(d, a) {
+                    set.union(down * width + across, d * width + a);
+                }
+            }
+        }
+    }
+    let mut labels: Vec<Vec<Option<usize>>> = vec![vec![None; width]; height];
+    let mut label_of_root: Vec<Option<usize>> = vec![None; height * width];
+    let mut basins: Vec<Basin> = Vec::new();
+    for (down, row) in labels.iter_mut().enumerate() {
+        for (across, cell) in row.iter_mut().enumerate() {
+            if !open(down, across) {
+                continue;
+            }
+            let root = set.find(down * width + across);
+            let here = map.0[down][across];
+            let label = *label_of_root[root].get_or_insert_with(|| {
+                basins.push(Basin { low: (down, across, here), cells: Vec::new() });
+                basins.len() - 1
+            });
+            let basin = &mut basins[label];
+            basin.cells.push((down, across));
+            if here < basin.low.2 {
+                basin.low = (down, across, here);
+            }
+            *cell = Some(label);
+        }
+    }
+    Basins {labels, basins}
+}
+
+
+fn find_basins(map: &Map, terrain: &Terrain) -> usize {
+    let basins = label_basins(map, terrain);
+    let mut with_low_point = vec![false; basins.basins.len()];
+    for &(down, across, _) in find_low_points(map, terrain).iter() {
+        if let Some(label) = basins.labels[down][across] {
+            with_low_point[label] = true;
+        }
     }
+    println!("Found {} basins, {} without a strict low point", basins.basins.len(),
+             with_low_point.iter().filter(|&&l| !l).count());
+    let mut sizes = basins.basins.iter().map(|b| b.size()).collect::<Vec<_>>();
     sizes.sort_by(|a,b| b.cmp(a));
     sizes.iter().take(3).product()
 }
 
 
-// find the basin size
-fn find_basin_size(map: &Map, point: &Point) -> u32 {
-    println!("--");
-    println!(" Find basin starting at: {:?}", &point);
-    let mut queue: Vec<Point> = Vec::new();
-    let mut count: u32 = 0;
-    let mut visited: HashSet<Point> = HashSet::new();
-    queue.push(*point);
-    while let Some(at) = queue.pop() {
-        if !visited.contains(&at) {
-            count += 1;
-            println!("Explore at: {:?}, count is: {}", &at, &count);
-            explore_at(&map, &mut queue, &mut visited, &at);
+// -------------------------------------------------------------------------------------------------
+// Analysis of the heightmap
+
+// A saddle is an open cell where, going round its neighbours, the ground goes up and down at
+// least twice, i.e. it's lower than neighbours on two sides and higher than those in between.
+fn find_saddle_points(map: &Map, terrain: &Terrain) -> Vec<Point> {
+    let mut saddles: Vec<Point> = Vec::new();
+    for (down, row) in map.0.iter().enumerate() {
+        for (across, &here) in row.iter().enumerate() {
+            if terrain.is_wall(here) {
+                continue;
+            }
+            // off the map is a gap in the ring, so there's no change across it.
+            let signs = terrain.neighbours(map, down, across).iter()
+                .map(|n| n.map(|(d, a)| map.0[d][a].cmp(&here)))
+                .filter(|o| o.is_none_or(|o| o.is_ne()))
+                .collect::<Vec<_>>();
+            let changes = (0..signs.len())
+                .filter(|&i| match (signs[i], signs[(i + 1) % signs.len()]) {
+                    (Some(s1), Some(s2)) => s1 != s2,
+                    _ => false,
+                })
+                .count();
+            if changes >= 4 {
+                saddles.push((down, across, here));
+            }
         }
     }
-    count
+    saddles
 }
 
 
-// explore at a point, adding any new points to the queue
-fn explore_at(map: &Map, queue: &mut Vec<Point>, visited: &mut HashSet<Point>, at: &Point) {
-    visited.insert(*at);
-    let (down, across, height) = *at;
-    let neighbour_heights = neighbours(&map, down, across); // [above, right, left, below]  // NEWS in the map
-    for (i, (dd, da)) in [(-1,0), (0,1), (0,-1), (1,0)].iter().enumerate() {
-        if neighbour_heights[i] < 9 && neighbour_heights[i] > height {
-            let new_down = ((down as i32) + dd) as usize;
-            let new_across = ((across as i32) + da) as usize;
-            println!("  new at: {}, {}, height: {}", &new_down, &new_across, &neighbour_heights[i]);
-            queue.push((new_down, new_across, neighbour_heights[i]));
+// The rim of a basin is its lowest neighbouring wall (or the wall level if it doesn't touch
+// one); its depth is the rim less the low point, and its volume is the water it holds when
+// filled to the rim.
+fn depth_and_volume(map: &Map, terrain: &Terrain, basin: &Basin) -> (u32, u32) {
+    let rim = basin.cells.iter()
+        .flat_map(|&(down, across)| terrain.neighbours(map, down, across))
+        .flatten()
+        .map(|(d, a)| map.0[d][a])
+        .filter(|&h| terrain.is_wall(h))
+        .min()
+        .unwrap_or(terrain.wall_level);
+    let volume = basin.cells.iter().map(|&(d, a)| rim - map.0[d][a]).sum();
+    (rim - basin.low.2, volume)
+}
+
+
+// the groups of basins that join up if the walls at the wall level become open.
+fn merges_if_lowered(map: &Map, terrain: &Terrain, basins: &Basins) -> Vec<Vec<usize>> {
+    let lowered = label_basins(map, &Terrain {wall_level: terrain.wall_level + 1, ..*terrain});
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); lowered.basins.len()];
+    for (label, basin) in basins.basins.iter().enumerate() {
+        let (d, a) = basin.cells[0];
+        if let Some(merged) = lowered.labels[d][a] {
+            groups[merged].push(label);
         }
     }
+    groups.into_iter().filter(|g| g.len() > 1).collect()
+}
+
+
+fn analyse(map: &Map, terrain: &Terrain) {
+    let basins = label_basins(map, terrain);
+    println!("{:?}; {} basins", terrain, basins.basins.len());
+    for (label, basin) in basins.basins.iter().enumerate() {
+        let (depth, volume) = depth_and_volume(map, terrain, basin);
+        println!("Basin {}: low point {:?}, size {}, depth {}, volume {}",
+                 label, basin.low, basin.size(), depth, volume);
+    }
+    let saddles = find_saddle_points(map, terrain);
+    println!("{} saddle points: {:?}", saddles.len(), saddles);
+    let merges = merges_if_lowered(map, terrain, &basins);
+    if merges.is_empty() {
+        println!("No basins merge if the walls at height {} open up", terrain.wall_level);
+    }
+    for group in merges.iter() {
+        println!("Basins {:?} merge if the walls at height {} open up", group, terrain.wall_level);
+    }
 }
 
-pub fn day9_1() {
+
+pub fn day9_1(options: &[String]) {
     println!("Day 9: Smoke Basin, part 1");
-    let lines = utils::read_file_single_result::<String>("./input/day09.txt").expect("Couldn't read file");
-    println!("Input: {:?}", &lines);
-    let map = parse_lines(&lines);
-    println!("Map: {:?}", &map);
-    let points = find_low_points(&map);
+    let file_name = utils::input_file(options, "./input/day09.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Couldn't read file");
+    let map = parse_lines(&lines).expect("Couldn't parse the map");
+    let terrain = Terrain::from_options(options).expect("Bad terrain options");
+    let points = find_low_points(&map, &terrain);
     let t: u32 = points.iter().map(|&(_,_,h)| h+1).sum();
     println!("Result is: {}", t);
 }
 
 
-pub fn day9_2() {
+pub fn day9_2(options: &[String]) {
     println!("Day 9: Smoke Basin, part 2");
-    let lines = utils::read_file_single_result::<String>("./input/day09.txt").expect("Couldn't read file");
-    println!("Input: {:?}", &lines);
-    let map = parse_lines(&lines);
-    println!("Map: {:?}", &map);
-    let sum = find_basins(&map);
+    let file_name = utils::input_file(options, "./input/day09.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Couldn't read file");
+    let map = parse_lines(&lines).expect("Couldn't parse the map");
+    let terrain = Terrain::from_options(options).expect("Bad terrain options");
+    if utils::has_option(options, "--analyse") {
+        analyse(&map, &terrain);
+    }
+    let sum = find_basins(&map, &terrain);
     println!("Result is: {}", sum);
 }
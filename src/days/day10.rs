@@ -7,120 +7,496 @@
 //All of them?! The damage is worse than you thought. You bring up a copy of the navigation subsystem (your puzzle input).
 
 
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
 use crate::utils;
 
 
+const DEFAULT_PAIRS: &str = "()[]{}<>";
+const DEFAULT_POINTS: [u64; 4] = [3, 57, 1197, 25137];
+
+
+/// The bracket pairs to check, e.g. "()[]{}<>", with the points for finding each closer where
+/// it shouldn't be (part 1).  The completion score (part 2) counts a pair as its position + 1,
+/// in base (number of pairs + 1).  Other characters are skipped unless 'strict' is set, when
+/// they're Invalid.
+#[derive(Clone, Debug)]
+struct Brackets {
+    pairs: Vec<(char, char)>,
+    points: Vec<u64>,
+    strict: bool,
+}
+
+
+impl Brackets {
+
+    fn new(pairs: &str, points: Vec<u64>) -> Result<Self, String> {
+        let chars = pairs.chars().collect::<Vec<_>>();
+        if chars.is_empty() || chars.len() % 2 != 0 {
+            return Err(format!("Bracket pairs must be an even number of characters: '{}'", pairs));
+        }
+        if (1..chars.len()).any(|i| chars[..i].contains(&chars[i])) {
+            return Err(format!("Bracket pairs can't repeat a character: '{}'", pairs));
+        }
+        let pairs = chars.chunks(2).map(|p| (p[0], p[1])).collect();
+        Ok(Self {pairs, points, strict: false})
+    }
+
+    fn from_options(options: &[String]) -> Result<Self, String> {
+        let pairs = utils::option_value::<String>(options, "--pairs")?;
+        let points = match utils::option_value::<String>(options, "--points")? {
+            Some(points) => points.split(',')
+                .map(|p| p.trim().parse::<u64>().map_err(|e| format!("Bad points '{}': {}", p, e)))
+                .collect::<Result<Vec<_>, _>>()?,
+            None if pairs.is_none() => DEFAULT_POINTS.to_vec(),
+            None => Vec::new(),
+        };
+        let mut brackets = Self::new(pairs.as_deref().unwrap_or(DEFAULT_PAIRS), points)?;
+        brackets.strict = utils::has_option(options, "--strict");
+        Ok(brackets)
+    }
+
+    fn closer_for(&self, c: char) -> Option<char> {
+        self.pairs.iter().find(|&&(o, _)| o == c).map(|&(_, cl)| cl)
+    }
+
+    fn opener_for(&self, c: char) -> Option<char> {
+        self.pairs.iter().find(|&&(_, cl)| cl == c).map(|&(o, _)| o)
+    }
+
+    fn corrupted_points(&self, c: char) -> Option<u64> {
+        self.pairs.iter().position(|&(_, cl)| cl == c).and_then(|i| self.points.get(i).copied())
+    }
+
+    fn completion_value(&self, c: char) -> u64 {
+        self.pairs.iter().position(|&(_, cl)| cl == c).map_or(0, |i| i as u64 + 1)
+    }
+}
+
+
+// columns count from 1.
 #[derive(Clone, Debug)]
 enum SyntaxResult {
-    Corrupted(char),
+    Corrupted {column: usize, found: char, expected: char},
     Incomplete(String),
-    Invalid(char),
+    Invalid(usize, char),
     Valid,
 }
 
 use SyntaxResult::*;
 
 
-fn parse_lines_p1(lines: &[String]) -> u32 {
-    let mut syntax_sum: u32 = 0;
-    for line in lines.iter() {
-        let result = check_line(&line);
-        println!("Line '{}' decodes as '{:?}'", &line, &result);
-        match result {
-            Corrupted(c) => {
-                if let Some(v) = char_to_value_p1(&c) {
-                    syntax_sum += v;
-                }
-            },
-            _ => {},
-        }
-    }
-    syntax_sum
-}
-
-
-fn parse_lines_p2(lines: &[String]) -> u64 {
-    let mut scores: Vec<u64> = Vec::new();
-    for line in lines.iter() {
-        let result = check_line(&line);
-        println!("Line '{}' decodes as '{:?}'", &line, &result);
-        match result {
-            Incomplete(s) => {
-                let v = s.chars().fold(0, |acc, c| {
-                    acc * 5 + match c {
-                        ')' => 1,
-                        ']' => 2,
-                        '}' => 3,
-                        '>' => 4,
-                        _ => 0,
-                    }
-                });
-                println!("The score is {}", v);
-                scores.push(v);
-            },
-            _ => {},
+// the lines of a reader, numbered from 1, with read errors as Strings.
+fn numbered_lines<R: BufRead>(reader: R) -> impl Iterator<Item=Result<(usize, String), String>> {
+    reader.lines()
+        .enumerate()
+        .map(|(i, line)| line.map(|l| (i + 1, l)).map_err(|e| format!("Couldn't read line {}: {}", i + 1, e)))
+}
+
+
+fn parse_lines_p1<R: BufRead>(reader: R, brackets: &Brackets) -> Result<u64, String> {
+    let mut syntax_sum: u64 = 0;
+    for line in numbered_lines(reader) {
+        let (number, line) = line?;
+        if let Corrupted {found, ..} = check_line(&line, brackets) {
+            if let Some(v) = brackets.corrupted_points(found) {
+                syntax_sum = syntax_sum.checked_add(v)
+                    .ok_or_else(|| format!("The syntax score overflowed at line {}", number))?;
+            }
         }
     }
-    scores.sort();
-    println!("Scores are: {:?}", scores);
-    scores[scores.len()/2]
+    Ok(syntax_sum)
 }
 
 
-/// parse a line of braces.  Return an error with the brace if it went wrong
-/// If the line is okay, return None, otherwise return the offending char.
-fn check_line(line: &String) -> SyntaxResult {
+// the completion score for the closers; None if it overflows.
+fn completion_score(closers: &str, brackets: &Brackets) -> Option<u64> {
+    let base = brackets.pairs.len() as u64 + 1;
+    closers.chars()
+        .try_fold(0u64, |acc, c| acc.checked_mul(base)?.checked_add(brackets.completion_value(c)))
+}
+
+
+/// A running median: the lower half of the values in a max-heap and the upper half in a
+/// min-heap, with the upper half holding the extra one when there's an odd number.  The median
+/// is then the smallest of the upper half, which is sorted[len / 2] as the puzzle wants.
+#[derive(Debug, Default)]
+struct RunningMedian {
+    low: BinaryHeap<u64>,
+    high: BinaryHeap<Reverse<u64>>,
+}
+
+
+impl RunningMedian {
+
+    fn push(&mut self, v: u64) {
+        match self.high.peek() {
+            Some(&Reverse(h)) if v < h => self.low.push(v),
+            _ => self.high.push(Reverse(v)),
+        }
+        if self.low.len() > self.high.len() {
+            self.high.push(Reverse(self.low.pop().unwrap()));
+        } else if self.high.len() > self.low.len() + 1 {
+            self.low.push(self.high.pop().unwrap().0);
+        }
+    }
+
+    fn median(&self) -> Option<u64> {
+        self.high.peek().map(|r| r.0)
+    }
+}
+
+
+fn parse_lines_p2<R: BufRead>(reader: R, brackets: &Brackets) -> Result<Option<u64>, String> {
+    let mut scores = RunningMedian::default();
+    for line in numbered_lines(reader) {
+        let (number, line) = line?;
+        if let Incomplete(closers) = check_line(&line, brackets) {
+            match completion_score(&closers, brackets) {
+                Some(v) => scores.push(v),
+                None => println!("Line {}: the completion score for {} closers overflows; skipped",
+                                 number, closers.chars().count()),
+            }
+        }
+    }
+    Ok(scores.median())
+}
+
+
+fn check_line(line: &str, brackets: &Brackets) -> SyntaxResult {
     let mut pairs: Vec<char> = Vec::new();
-    for c in line.chars() {
-        match c {
-            '{' => { pairs.push('}') },
-            '(' => { pairs.push(')') },
-            '<' => { pairs.push('>') },
-            '[' => { pairs.push(']') },
-            '}' | ')' | '>' | ']' => {
-                if let Some(r) = pairs.pop() {
-                    if r != c {
-                        return Corrupted(c);
-                    }
-                } else {
-                    // Received more closes that opens?
-                    return Invalid(c);
-                }
-            },
-            _ => return Invalid(c),
+    for (i, c) in line.chars().enumerate() {
+        if let Some(closer) = brackets.closer_for(c) {
+            pairs.push(closer);
+        } else if brackets.opener_for(c).is_some() {
+            match pairs.pop() {
+                Some(r) if r != c => return Corrupted {column: i + 1, found: c, expected: r},
+                Some(_) => {},
+                // Received more closes that opens?
+                None => return Invalid(i + 1, c),
+            }
+        } else if brackets.strict {
+            return Invalid(i + 1, c);
         }
     }
-    if pairs.len() > 0 {
+    if !pairs.is_empty() {
         return Incomplete(pairs.iter().rev().collect());
     }
     Valid
 }
 
 
-fn char_to_value_p1(c: &char) -> Option<u32> {
-    match c {
-        ')' => Some(3),
-        ']' => Some(57),
-        '}' => Some(1197),
-        '>' => Some(25137),
-        _ => None,
+// -------------------------------------------------------------------------------------------------
+// Autocorrect
+//
+// The fewest edits (inserting, deleting or replacing a character) that turn a line into one
+// that isn't corrupted, i.e. a balanced line that may have openers still waiting to be closed.
+// Characters that aren't brackets are kept as they are unless strict.  'balanced[i][j]' is the
+// fewest edits for chars[i..j] to be balanced, and 'prefix[i]' for chars[i..] to be a valid
+// start of a line.  Both are O(n^3) in the line length, so lines longer than MAX_EXACT_LENGTH
+// get a greedy repair instead, which is linear but won't always find the fewest edits.
+
+const MAX_EXACT_LENGTH: usize = 500;
+
+
+struct Corrector<'a> {
+    brackets: &'a Brackets,
+    chars: Vec<char>,
+    balanced: Vec<Vec<usize>>,
+    prefix: Vec<usize>,
+}
+
+
+#[derive(Clone, Copy, Debug)]
+enum Choice {
+    Keep,
+    Delete,
+    InsertOpener(char),
+    InsertCloser(usize, char),
+    Pair(usize, char, char),
+}
+
+
+// what's still to be written out when building the corrected line.
+#[derive(Clone, Copy, Debug)]
+enum Task {
+    Balanced(usize, usize),
+    Prefix(usize),
+    Emit(char),
+}
+
+
+// keep the cheapest choice; a tie goes to the later one, which keeps more characters.
+fn consider(best: &mut (usize, Choice), choice: (usize, Choice)) {
+    if choice.0 <= best.0 {
+        *best = choice;
     }
 }
 
 
-pub fn day10_1() {
+impl<'a> Corrector<'a> {
+
+    fn new(line: &str, brackets: &'a Brackets) -> Self {
+        let chars = line.chars().collect::<Vec<_>>();
+        let n = chars.len();
+        let mut corrector = Self {brackets, chars, balanced: vec![vec![0; n + 1]; n + 1], prefix: vec![0; n + 1]};
+        for i in (0..n).rev() {
+            for j in i + 1..=n {
+                corrector.balanced[i][j] = corrector.balanced_best(i, j).0;
+            }
+        }
+        for i in (0..n).rev() {
+            corrector.prefix[i] = corrector.prefix_best(i).0;
+        }
+        corrector
+    }
+
+    // a character that's left alone: not a bracket, and not strict.
+    fn skipped(&self, i: usize) -> bool {
+        let c = self.chars[i];
+        !self.brackets.strict && self.brackets.closer_for(c).is_none() && self.brackets.opener_for(c).is_none()
+    }
+
+    // the edits to pair chars[i] with chars[k] and the opener and closer they'd become.
+    fn pair(&self, i: usize, k: usize) -> (usize, char, char) {
+        let (a, b) = (self.chars[i], self.chars[k]);
+        match (self.brackets.closer_for(a), self.brackets.opener_for(b)) {
+            (Some(closer), _) => (usize::from(closer != b), a, closer),
+            (None, Some(opener)) => (1, opener, b),
+            (None, None) => {
+                let (o, c) = self.brackets.pairs[0];
+                (2, o, c)
+            },
+        }
+    }
+
+    // the cheapest way to make chars[i..j] balanced: (edits, what to do with chars[i]).
+    fn balanced_best(&self, i: usize, j: usize) -> (usize, Choice) {
+        if self.skipped(i) {
+            return (self.balanced[i + 1][j], Choice::Keep);
+        }
+        let c = self.chars[i];
+        let mut best = (1 + self.balanced[i + 1][j], Choice::Delete);
+        if let Some(opener) = self.brackets.opener_for(c) {
+            consider(&mut best, (1 + self.balanced[i + 1][j], Choice::InsertOpener(opener)));
+        }
+        if let Some(closer) = self.brackets.closer_for(c) {
+            for k in i + 1..=j {
+                consider(&mut best, (1 + self.balanced[i + 1][k] + self.balanced[k][j], Choice::InsertCloser(k, closer)));
+            }
+        }
+        for k in (i + 1..j).filter(|&k| !self.skipped(k)) {
+            let (edits, opener, closer) = self.pair(i, k);
+            consider(&mut best, (edits + self.balanced[i + 1][k] + self.balanced[k + 1][j], Choice::Pair(k, opener, closer)));
+        }
+        best
+    }
+
+    // the cheapest way to make chars[i..] a valid start of a line.
+    fn prefix_best(&self, i: usize) -> (usize, Choice) {
+        if self.skipped(i) {
+            return (self.prefix[i + 1], Choice::Keep);
+        }
+        let n = self.chars.len();
+        let c = self.chars[i];
+        let mut best = (1 + self.prefix[i + 1], Choice::Delete);
+        if self.brackets.closer_for(c).is_some() {
+            consider(&mut best, (self.prefix[i + 1], Choice::Keep));
+        } else if let Some(opener) = self.brackets.opener_for(c) {
+            consider(&mut best, (1 + self.prefix[i + 1], Choice::InsertOpener(opener)));
+        }
+        for k in (i + 1..n).filter(|&k| !self.skipped(k)) {
+            let (edits, opener, closer) = self.pair(i, k);
+            consider(&mut best, (edits + self.balanced[i + 1][k] + self.prefix[k + 1], Choice::Pair(k, opener, closer)));
+        }
+        best
+    }
+
+    /// the corrected line and how many edits it took.
+    fn correct(&self) -> (String, usize) {
+        let n = self.chars.len();
+        let mut out = String::with_capacity(n);
+        let mut tasks = vec![Task::Prefix(0)];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Emit(c) => out.push(c),
+                Task::Balanced(i, j) if i >= j => {},
+                Task::Balanced(i, j) => match self.balanced_best(i, j).1 {
+                    Choice::Keep => {
+                        out.push(self.chars[i]);
+                        tasks.push(Task::Balanced(i + 1, j));
+                    },
+                    Choice::Delete => tasks.push(Task::Balanced(i + 1, j)),
+                    Choice::InsertOpener(opener) => {
+                        out.push(opener);
+                        out.push(self.chars[i]);
+                        tasks.push(Task::Balanced(i + 1, j));
+                    },
+                    Choice::InsertCloser(k, closer) => {
+                        out.push(self.chars[i]);
+                        tasks.extend([Task::Balanced(k, j), Task::Emit(closer), Task::Balanced(i + 1, k)]);
+                    },
+                    Choice::Pair(k, opener, closer) => {
+                        out.push(opener);
+                        tasks.extend([Task::Balanced(k + 1, j), Task::Emit(closer), Task::Balanced(i + 1, k)]);
+                    },
+                },
+                Task::Prefix(i) if i >= n => {},
+                Task::Prefix(i) => match self.prefix_best(i).1 {
+                    Choice::Keep => {
+                        out.push(self.chars[i]);
+                        tasks.push(Task::Prefix(i + 1));
+                    },
+                    Choice::Delete => tasks.push(Task::Prefix(i + 1)),
+                    Choice::InsertOpener(opener) => {
+                        out.push(opener);
+                        out.push(self.chars[i]);
+                        tasks.push(Task::Prefix(i + 1));
+                    },
+                    Choice::Pair(k, opener, closer) => {
+                        out.push(opener);
+                        tasks.extend([Task::Prefix(k + 1), Task::Emit(closer), Task::Balanced(i + 1, k)]);
+                    },
+                    Choice::InsertCloser(..) => unreachable!(),
+                },
+            }
+        }
+        (out, self.prefix[0])
+    }
+}
+
+
+// A single pass with the stack of closers that are expected: a closer that doesn't match the top
+// of the stack either gets the missing closer inserted in front of it (if it matches the one
+// below), or is replaced by the expected closer; a closer with nothing open is deleted, as are
+// other characters when strict.
+fn greedy_repair(line: &str, brackets: &Brackets) -> (String, usize) {
+    let mut fixed = String::with_capacity(line.len());
+    let mut stack: Vec<char> = Vec::new();
+    let mut edits: usize = 0;
+    for c in line.chars() {
+        if let Some(closer) = brackets.closer_for(c) {
+            stack.push(closer);
+            fixed.push(c);
+        } else if brackets.opener_for(c).is_some() {
+            match stack.pop() {
+                Some(top) if top == c => fixed.push(c),
+                Some(top) if stack.last() == Some(&c) => {
+                    stack.pop();
+                    fixed.push(top);
+                    fixed.push(c);
+                    edits += 1;
+                },
+                Some(top) => {
+                    fixed.push(top);
+                    edits += 1;
+                },
+                None => edits += 1,
+            }
+        } else if brackets.strict {
+            edits += 1;
+        } else {
+            fixed.push(c);
+        }
+    }
+    (fixed, edits)
+}
+
+
+/// A corrected line: the edits it took and the closers added to complete it.  'minimal' is
+/// false if the line was too long for the exact search and the greedy repair was used.
+#[derive(Clone, Debug)]
+struct Correction {
+    line: String,
+    edits: usize,
+    closers: usize,
+    minimal: bool,
+}
+
+
+/// Autocorrect a line: an incomplete line is completed, and a corrupted (or invalid) one gets
+/// the fewest edits to stop it being corrupted and is then completed.  None if it's valid.
+fn autocorrect(line: &str, brackets: &Brackets) -> Option<Correction> {
+    let length = line.chars().count();
+    let ((fixed, edits), minimal) = match check_line(line, brackets) {
+        Valid => return None,
+        Incomplete(_) => ((line.to_string(), 0), true),
+        Corrupted {..} | Invalid(..) if length > MAX_EXACT_LENGTH => (greedy_repair(line, brackets), false),
+        Corrupted {..} | Invalid(..) => (Corrector::new(line, brackets).correct(), true),
+    };
+    Some(match check_line(&fixed, brackets) {
+        Incomplete(closers) => Correction {line: format!("{}{}", fixed, closers), edits, closers: closers.chars().count(), minimal},
+        _ => Correction {line: fixed, edits, closers: 0, minimal},
+    })
+}
+
+
+fn describe(result: &SyntaxResult) -> String {
+    match result {
+        Corrupted {column, found, expected} => format!("column {}: expected '{}' but found '{}'", column, expected, found),
+        Incomplete(closers) => format!("incomplete: needs '{}'", closers),
+        Invalid(column, c) => format!("column {}: unexpected '{}'", column, c),
+        Valid => "valid".to_string(),
+    }
+}
+
+
+// lint the lines, printing what's wrong with each and how to fix it.
+fn lint<R: BufRead>(reader: R, brackets: &Brackets) -> Result<(), String> {
+    for line in numbered_lines(reader) {
+        let (number, line) = line?;
+        let result = check_line(&line, brackets);
+        if let Valid = result {
+            continue;
+        }
+        println!("Line {}, {}", number, describe(&result));
+        if let Some(fix) = autocorrect(&line, brackets) {
+            println!("  {} edit{} and {} closer{}{}: {}", fix.edits, if fix.edits == 1 { "" } else { "s" },
+                     fix.closers, if fix.closers == 1 { "" } else { "s" },
+                     if fix.minimal { "" } else { " (greedy, maybe not the fewest edits)" }, fix.line);
+        }
+    }
+    Ok(())
+}
+
+
+fn open_input(options: &[String]) -> BufReader<File> {
+    let file_name = utils::input_file(options, "./input/day10.txt");
+    BufReader::new(File::open(&file_name).expect("Couldn't read file"))
+}
+
+
+pub fn day10_1(options: &[String]) {
     println!("Day 10: Syntax Scoring, part 1");
-    let lines = utils::read_file_single_result::<String>("./input/day10.txt").expect("Couldn't read file");
-    println!("Input: {:?}", &lines);
-    let res = parse_lines_p1(&lines);
-    println!("Syntax sum is {}", res);
+    let brackets = Brackets::from_options(options).expect("Bad bracket options");
+    if utils::has_option(options, "--fix") {
+        if let Err(e) = lint(open_input(options), &brackets) {
+            println!("{}", e);
+        }
+        return;
+    }
+    match parse_lines_p1(open_input(options), &brackets) {
+        Ok(res) => println!("Syntax sum is {}", res),
+        Err(e) => println!("{}", e),
+    }
 }
 
 
-pub fn day10_2() {
+pub fn day10_2(options: &[String]) {
     println!("Day 10: Syntax Scoring, part 2");
-    let lines = utils::read_file_single_result::<String>("./input/day10.txt").expect("Couldn't read file");
-    println!("Input: {:?}", &lines);
-    let res = parse_lines_p2(&lines);
-    println!("Syntax sum is {}", res);
+    let brackets = Brackets::from_options(options).expect("Bad bracket options");
+    if utils::has_option(options, "--fix") {
+        if let Err(e) = lint(open_input(options), &brackets) {
+            println!("{}", e);
+        }
+        return;
+    }
+    match parse_lines_p2(open_input(options), &brackets) {
+        Ok(Some(res)) => println!("Syntax sum is {}", res),
+        Ok(None) => println!("There are no incomplete lines"),
+        Err(e) => println!("{}", e),
+    }
 }
@@ -5,144 +5,132 @@
 //There are 100 octopuses arranged neatly in a 10 by 10 grid. Each octopus slowly gains energy over time and flashes brightly for a moment when its energy is full. Although your lights are off, maybe you could navigate through the cave without disturbing the octopuses if you could predict when the flashes of light will happen.
 
 
+use crate::automaton::{Automaton, Boundary, Cascade, Ending, Grid, History, Neighbourhood, Rule};
 use crate::utils;
 
 
-// we need to model the octopuses.
-
-#[derive(Clone, Debug)]
-struct Octos {
-    height: usize,
-    width: usize,
-    map: Vec<Vec<u32>>,
-    flashed: Vec<Vec<bool>>,
+// Each step every octopus gains 1 energy, and then any over the threshold flash, once each,
+// giving 1 energy to each octopus around them.  Those that flashed go back to 0.
+struct Octopuses {
+    threshold: u32,
+    neighbourhood: Neighbourhood,
 }
 
 
-fn parse_lines(lines: &[String]) -> Octos {
-    let height = lines.len();
-    let width = lines[0].len();
-    let mut map: Vec<Vec<u32>> = Vec::with_capacity(height);
-    for line in lines.iter() {
-        if line.len() != width {
-            panic!("Line length didn't match.");
-        }
-        map.push(line.chars().map(|c| c.to_digit(10).unwrap()).collect::<Vec<_>>());
+impl Rule for Octopuses {
+    type Cell = u32;
+
+    fn phases(&self) -> Vec<Neighbourhood> {
+        vec![Neighbourhood::Offsets(vec![])]
     }
-    Octos { height, width, map, flashed: vec![]}
-}
 
+    fn next(&self, _phase: usize, cell: &u32, _neighbours: &[Option<&u32>]) -> u32 {
+        cell + 1
+    }
 
-fn inc_map(octos: &mut Octos) {
-    for down in 0..octos.height {
-        for across in 0..octos.width {
-            octos.map[down][across] += 1;
-        }
+    fn cascade(&self) -> Cascade {
+        Cascade::OncePerStep
     }
-    octos.flashed = vec![vec![false; octos.width]; octos.height];
-}
 
+    fn cascade_neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood.clone()
+    }
 
-// see if anything needs to flash, and if so then flash them.
-// returns true if flashed, which means it might need to be called again.
-// Also does the increments for the neighbours, and sets any that flashed
-// to flashed.
-fn check_for_flash(octos: &mut Octos) -> u32 {
-    let mut to_flash: Vec<(usize, usize)> = Vec::new();
-    let mut flashed: u32 = 0;
-    // check for octopuses that will flash
-    for down in 0..octos.height {
-        for across in 0..octos.width {
-            if !octos.flashed[down][across] && octos.map[down][across] > 9 {
-                to_flash.push((down, across));
-                octos.flashed[down][across] = true;
-                octos.map[down][across] = 0;
-                flashed += 1;
-            }
-        }
+    fn fires(&self, cell: &u32) -> bool {
+        *cell > self.threshold
     }
-    // now increment all the neighbours that haven't flashed
-    for (down, across) in to_flash.iter() {
-        for (nd, na) in neighbours(octos.height, octos.width, *down, *across).iter() {
-            if !octos.flashed[*nd][*na] {
-                octos.map[*nd][*na] += 1;
-            }
-        }
+
+    fn fired(&self, _cell: &u32) -> u32 {
+        0
+    }
+
+    fn receive(&self, cell: &u32) -> u32 {
+        cell + 1
     }
-    flashed
 }
 
 
-// return a list of neighbours as (down, across) for a map
-fn neighbours(height: usize, width: usize, down: usize, across: usize) -> Vec<(usize, usize)> {
-    let mut neighbours: Vec<(usize, usize)> = Vec::with_capacity(8);
-    for dd in [-1, 0, 1].iter() {
-        for da in [-1, 0, 1].iter() {
-            let d = (down as i32) + dd;
-            let a = (across as i32) + da;
-            if d >= 0 && d < (height as i32) && a >= 0 && a < (width as i32) {
-                neighbours.push((d as usize, a as usize));
-            }
-        }
-    }
-    neighbours
+fn parse_lines(lines: &[String]) -> Result<Grid<u32>, String> {
+    let rows = lines.iter()
+        .map(|line| line.chars()
+             .map(|c| c.to_digit(10).ok_or_else(|| format!("'{}' isn't an energy level", c)))
+             .collect::<Result<Vec<_>, _>>())
+        .collect::<Result<Vec<_>, _>>()?;
+    Grid::new(rows, 0)
 }
 
 
-fn do_step(octos: &mut Octos) -> u32 {
-    inc_map(octos);
-    let mut total_flashes: u32 = 0;
-    loop {
-        let flashes = check_for_flash(octos);
-        total_flashes += flashes;
-        if flashes == 0 {
-            break;
-        }
-    }
-    total_flashes
+// the octopuses from the input with the --threshold, --neighbours and --boundary options.
+fn octopuses(options: &[String]) -> Automaton<Octopuses> {
+    let file_name = utils::input_file(options, "./input/day11.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Couldn't read file");
+    let grid = parse_lines(&lines).expect("Couldn't parse the octopuses");
+    let threshold = utils::option_value::<u32>(options, "--threshold")
+        .expect("Bad --threshold option")
+        .unwrap_or(9);
+    let neighbourhood = utils::option_value::<Neighbourhood>(options, "--neighbours")
+        .expect("Bad --neighbours option")
+        .unwrap_or(Neighbourhood::Moore);
+    let boundary = utils::option_value::<Boundary>(options, "--boundary")
+        .expect("Bad --boundary option")
+        .unwrap_or(Boundary::Clipped);
+    Automaton::new(Octopuses {threshold, neighbourhood}, grid, boundary).expect("Couldn't make the automaton")
 }
 
-fn print_map(octos: &Octos) {
-    for down in 0..octos.height {
-        println!("{}", octos.map[down].iter().map(|d| d.to_string()).collect::<String>());
+
+// the steps to look at for a cycle, or for them all to flash, before giving up; --limit
+// overrides it.
+const DEFAULT_LIMIT: usize = 1_000_000;
+
+
+fn step_limit(options: &[String]) -> usize {
+    utils::option_value::<usize>(options, "--limit")
+        .expect("Bad --limit option")
+        .unwrap_or(DEFAULT_LIMIT)
+}
+
+
+fn explore(octos: &mut Automaton<Octopuses>, options: &[String], steps: usize) -> History {
+    let history = octos.history(step_limit(options).min(steps));
+    match history.cycle {
+        Some((transient, period)) => println!("The octopuses repeat after {} steps, every {} steps", transient, period),
+        None => println!("No cycle found in {} steps", history.stats.len()),
     }
+    history
 }
 
-pub fn day11_1() {
+
+pub fn day11_1(options: &[String]) {
     println!("Day 11: Dumbo Octopus, part 1");
-    let lines = utils::read_file_single_result::<String>("./input/day11.txt").expect("Couldn't read file");
-    println!("Input: {:?}", &lines);
-    let mut map = parse_lines(&lines);
-    print_map(&map);
-    let mut all_flashes: u32 = 0;
-    for _i in 0..100 {
-        all_flashes += do_step(&mut map);
+    let mut octos = octopuses(options);
+    print!("{}", octos.grid);
+    let steps = utils::option_value::<u64>(options, "--steps")
+        .expect("Bad --steps option")
+        .unwrap_or(100);
+    let history = explore(&mut octos, options, usize::try_from(steps).unwrap_or(usize::MAX));
+    match history.total(steps, |s| s.fired) {
+        Some(all_flashes) => println!("Total flashes after {} steps: {}", steps, all_flashes),
+        None => println!("Can't work out the flashes after {} steps without finding a cycle", steps),
     }
-    println!("after 100 steps:");
-    print_map(&map);
-    println!("Total flashes: {}", all_flashes);
 }
 
 
-pub fn day11_2() {
+pub fn day11_2(options: &[String]) {
     println!("Day 11: Dumbo Octopus, part 2");
-    let lines = utils::read_file_single_result::<String>("./input/day11.txt").expect("Couldn't read file");
-    println!("Input: {:?}", &lines);
-    let mut map = parse_lines(&lines);
-    print_map(&map);
-    let size = (map.height * map.width) as u32;
-    let mut all_flashes: u32 = 0;
-    let mut steps: u32 = 0;
-    loop {
-        steps += 1;
-        let flashes = do_step(&mut map);
-        all_flashes += flashes;
-        if flashes == size {
-            break;
-        }
+    let mut octos = octopuses(options);
+    print!("{}", octos.grid);
+    let size = octos.grid.cells.len();
+    let limit = step_limit(options);
+    let mut flashes: u128 = 0;
+    let ending = octos.run_to_cycle(limit, |_, stats| {
+        flashes += stats.fired as u128;
+        stats.fired == size
+    });
+    match ending {
+        Ending::Done(step) => println!("All the octopuses flash at step {}, after {} flashes", step, flashes),
+        Ending::Cycle(transient, period) => println!(
+            "The octopuses never all flash at once: they cycle every {} steps after {} without doing so",
+            period, transient),
+        Ending::Limit => println!("The octopuses didn't all flash at once in {} steps", limit),
     }
-    println!("after {} steps:", steps);
-    print_map(&map);
-    println!("Total flashes: {}", all_flashes);
 }
-
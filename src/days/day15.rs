@@ -4,10 +4,6 @@
 
 
 use std::fmt;
-use std::collections::HashMap;
-use std::cmp::Reverse;
-
-use priority_queue::PriorityQueue;
 
 use crate::utils;
 
@@ -95,75 +91,28 @@ impl MapAt for VirtualMap {
 
 // ---
 
-#[derive(Clone, Debug, Eq, Hash)]
-struct Item {
-    down: usize,
-    across: usize,
-    cost: usize,
-}
-
-
-// two items are equal if there are in the same place.
-impl PartialEq for Item {
-    fn eq(&self, other: &Self) -> bool {
-        self.down == other.down && self.across == other.across
-    }
-}
-
-// find the least costly path by using a prioity queue to search from 0,0 to maxd, maxa.
-// The priority will be the current path cost + the manhatten distance to the end.
+// find the least costly path from 0,0 to maxd, maxa using the shared least cost search.
+// The heuristic is the manhatten distance to the end as every step costs at least 1.
 fn least_costly_path(map: &dyn MapAt) -> usize {
     let (down_max, across_max) = map.bounds();
-    let mut pq = PriorityQueue::new();
-    let mut been_there: HashMap<(usize, usize), usize> = HashMap::new();
-    //pq.push(Item {down: 0, across: 0, cost: 0}, distance(&map, 0, 0));
-    pq.push(Item {down: 0, across: 0, cost: 0}, Reverse(0));
-    been_there.insert((0,0), 0);
-
-    // now loop taking the least cost route and moving it forwards.
-    while let Some((item, _)) = pq.pop() {
-        let cost = map.at(item.down, item.across).unwrap();
-        println!("Item is {:?}, cost there: {}, pq length is: {}", &item, &cost, pq.len());
+    let end = (down_max - 1, across_max - 1);
+    let neighbours = |&(down, across): &(usize, usize)| {
+        let mut next: Vec<((usize, usize), usize)> = Vec::with_capacity(4);
         for &(dd, da) in [(-1,0),(1,0),(0,-1),(0,1)].iter() {
-            let dn = dd + (item.down as i32);
-            let an = da + (item.across as i32);
-            if dn < 0 {
-                continue;
-            }
-            if (dn as usize) >= down_max {
+            let dn = dd + (down as i32);
+            let an = da + (across as i32);
+            if dn < 0 || (dn as usize) >= down_max || an < 0 || (an as usize) >= across_max {
                 continue;
             }
-            if an < 0 {
-                continue;
-            }
-            if (an as usize) >= across_max {
-                continue;
-            }
-            // see if we've reached the end.
-            let udn = dn as usize;
-            let uan = an as usize;
-            if udn == down_max -1 && uan == across_max -1 {
-                return item.cost + (map.at(udn, uan).unwrap() as usize);
-            }
-            // otherwise push it as a new locations, only replacing an existing on
-            // if it costs less.
-            let n_item = Item { down: udn, across: uan, cost: item.cost + (map.at(udn, uan).unwrap() as usize) };
-            if let Some(prev_cost) = been_there.get(&(udn, uan)) {
-                if *prev_cost < n_item.cost {
-                    continue;
-                }
-            }
-            been_there.insert((udn, uan), n_item.cost);
-            if let Some((c_item, _)) = pq.get(&n_item) {
-                if c_item.cost < n_item.cost {
-                    continue;
-                }
-            }
-            let cost = n_item.cost;
-            pq.push(n_item, Reverse(cost));
+            let (udn, uan) = (dn as usize, an as usize);
+            next.push(((udn, uan), map.at(udn, uan).unwrap() as usize));
         }
-    }
-    panic!("No more items and not reached end!")
+        next
+    };
+    let distance = |&(down, across): &(usize, usize)| (end.0 - down) + (end.1 - across);
+    utils::least_cost_path((0, 0), neighbours, |&p| p == end, distance)
+        .map(|(cost, _)| cost)
+        .expect("No more items and not reached end!")
 }
 
 pub fn day15_1() {
@@ -0,0 +1,263 @@
+//--- Day 23: Amphipod ---
+
+//A group of amphipods notice your fancy submarine and flag you down. "With such an impressive
+//shell," one amphipod says, "surely you can help us with a question that has stumped our best
+//scientists."
+
+//They go on to explain that a group of timid, stubborn amphipods live in a nearby burrow. Four
+//types of amphipods live there: Amber (A), Bronze (B), Copper (C), and Desert (D). They live in a
+//burrow that consists of a hallway and four side rooms.
+
+use std::fmt;
+
+use crate::utils;
+
+
+const HALL_LEN: usize = 11;
+const NUM_ROOMS: usize = 4;
+const MAX_DEPTH: usize = 4;
+const EMPTY: u8 = 0;
+// energy per step for A, B, C and D (amphipods are stored as 1..=4)
+const ENERGY: [usize; NUM_ROOMS] = [1, 10, 100, 1000];
+// the extra two lines that are 'unfolded' into the diagram for part 2
+const UNFOLDED: [&str; 2] = ["  #D#C#B#A#", "  #D#B#A#C#"];
+
+
+// The burrow is small enough to just hold every cell; an amphipod is 1..=4 (A..=D) and an empty
+// cell is 0.  Rooms are indexed from the top (next to the hallway) downwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Burrow {
+    hall: [u8; HALL_LEN],
+    rooms: [[u8; MAX_DEPTH]; NUM_ROOMS],
+    depth: usize,
+}
+
+
+// the hallway position outside of a room.
+fn room_x(room: usize) -> usize {
+    2 + room * 2
+}
+
+
+fn is_outside_room(x: usize) -> bool {
+    matches!(x, 2 | 4 | 6 | 8)
+}
+
+
+fn to_char(cell: u8) -> char {
+    match cell {
+        EMPTY => '.',
+        c => (b'A' + c - 1) as char,
+    }
+}
+
+
+impl fmt::Display for Burrow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#############")?;
+        writeln!(f, "#{}#", self.hall.iter().map(|&c| to_char(c)).collect::<String>())?;
+        for d in 0..self.depth {
+            let cells = (0..NUM_ROOMS).map(|r| to_char(self.rooms[r][d]).to_string())
+                                      .collect::<Vec<_>>()
+                                      .join("#");
+            if d == 0 {
+                writeln!(f, "###{}###", cells)?;
+            } else {
+                writeln!(f, "  #{}#", cells)?;
+            }
+        }
+        write!(f, "  #########")
+    }
+}
+
+
+impl Burrow {
+
+    // parse the diagram; the room lines are any lines after the hallway that contain amphipods.
+    fn parse<S>(lines: &[S]) -> Result<Self, String>
+        where S: AsRef<str>
+    {
+        let mut rooms = [[EMPTY; MAX_DEPTH]; NUM_ROOMS];
+        let mut depth: usize = 0;
+        for line in lines.iter().skip(2) {
+            let cells = line.as_ref()
+                .chars()
+                .filter(|c| c.is_ascii_uppercase())
+                .collect::<Vec<_>>();
+            if cells.is_empty() {
+                continue;
+            }
+            if cells.len() != NUM_ROOMS {
+                return Err(format!("Room line doesn't have {} amphipods: {}", NUM_ROOMS, line.as_ref()));
+            }
+            if depth == MAX_DEPTH {
+                return Err(format!("More than {} room lines?", MAX_DEPTH));
+            }
+            for (r, &c) in cells.iter().enumerate() {
+                if !('A'..='D').contains(&c) {
+                    return Err(format!("'{}' isn't an amphipod in: {}", c, line.as_ref()));
+                }
+                rooms[r][depth] = (c as u8) - b'A' + 1;
+            }
+            depth += 1;
+        }
+        if depth == 0 {
+            return Err("No rooms found in the diagram?".to_string());
+        }
+        Ok(Self {hall: [EMPTY; HALL_LEN], rooms, depth})
+    }
+
+    fn is_done(&self) -> bool {
+        (0..NUM_ROOMS).all(|r| self.rooms[r][..self.depth].iter().all(|&c| c as usize == r + 1))
+    }
+
+    // a room can be entered if it only contains its own kind of amphipod.
+    fn room_is_open(&self, room: usize) -> bool {
+        self.rooms[room][..self.depth].iter().all(|&c| c == EMPTY || c as usize == room + 1)
+    }
+
+    // is the hallway clear from 'from' to 'to', not including 'from' itself.
+    fn hall_is_clear(&self, from: usize, to: usize) -> bool {
+        let (lo, hi) = if from < to { (from + 1, to) } else { (to, from - 1) };
+        self.hall[lo..=hi].iter().all(|&c| c == EMPTY)
+    }
+
+    // All the legal moves from this burrow with the energy they cost.  Amphipods either leave a
+    // room to stop in the hallway, or go from the hallway to the bottom of their own room.
+    fn moves(&self) -> Vec<(Burrow, usize)> {
+        let mut moves: Vec<(Burrow, usize)> = Vec::new();
+        // hallway -> room
+        for x in 0..HALL_LEN {
+            let a = self.hall[x];
+            if a == EMPTY {
+                continue;
+            }
+            let room = (a - 1) as usize;
+            let rx = room_x(room);
+            if !self.room_is_open(room) || !self.hall_is_clear(x, rx) {
+                continue;
+            }
+            let d = (0..self.depth).rev().find(|&d| self.rooms[room][d] == EMPTY).unwrap();
+            let mut next = *self;
+            next.hall[x] = EMPTY;
+            next.rooms[room][d] = a;
+            let steps = x.abs_diff(rx) + d + 1;
+            moves.push((next, steps * ENERGY[room]));
+        }
+        // room -> hallway
+        for room in 0..NUM_ROOMS {
+            if self.room_is_open(room) {
+                continue;
+            }
+            let d = match (0..self.depth).find(|&d| self.rooms[room][d] != EMPTY) {
+                Some(d) => d,
+                None => continue,
+            };
+            let a = self.rooms[room][d];
+            let rx = room_x(room);
+            for x in (0..HALL_LEN).filter(|&x| !is_outside_room(x)) {
+                if !self.hall_is_clear(rx, x) {
+                    continue;
+                }
+                let mut next = *self;
+                next.rooms[room][d] = EMPTY;
+                next.hall[x] = a;
+                let steps = x.abs_diff(rx) + d + 1;
+                moves.push((next, steps * ENERGY[(a - 1) as usize]));
+            }
+        }
+        moves
+    }
+
+    // A lower bound for the remaining energy: every amphipod not in its own room has to at least
+    // walk to the top of that room.
+    fn min_energy_left(&self) -> usize {
+        let mut energy: usize = 0;
+        for (x, &a) in self.hall.iter().enumerate() {
+            if a != EMPTY {
+                let room = (a - 1) as usize;
+                energy += (x.abs_diff(room_x(room)) + 1) * ENERGY[room];
+            }
+        }
+        for room in 0..NUM_ROOMS {
+            for d in 0..self.depth {
+                let a = self.rooms[room][d];
+                if a != EMPTY && (a - 1) as usize != room {
+                    let target = (a - 1) as usize;
+                    energy += (d + 1 + room_x(room).abs_diff(room_x(target)) + 1) * ENERGY[target];
+                }
+            }
+        }
+        energy
+    }
+}
+
+
+// unfold the diagram for part 2 by inserting the two extra lines after the first room line.
+fn unfold<S>(lines: &[S]) -> Vec<String>
+    where S: AsRef<str>
+{
+    let mut unfolded = lines.iter().map(|l| l.as_ref().to_string()).collect::<Vec<_>>();
+    let at = 3.min(unfolded.len());
+    for (i, line) in UNFOLDED.iter().enumerate() {
+        unfolded.insert(at + i, line.to_string());
+    }
+    unfolded
+}
+
+
+// find the least energy to organise the amphipods; returns the energy and each burrow on the way.
+fn least_energy(burrow: &Burrow) -> Option<(usize, Vec<Burrow>)> {
+    utils::least_cost_path(*burrow, |b| b.moves(), |b| b.is_done(), |b| b.min_energy_left())
+}
+
+
+fn print_trace(path: &[Burrow]) {
+    let mut total: usize = 0;
+    let mut prev: Option<&Burrow> = None;
+    for burrow in path.iter() {
+        if let Some(p) = prev {
+            let cost = p.moves().iter()
+                .find(|(b, _)| b == burrow)
+                .map(|(_, c)| *c)
+                .unwrap_or(0);
+            total += cost;
+            println!("Move costs {}, total {}:", cost, total);
+        } else {
+            println!("Start:");
+        }
+        println!("{}\n", burrow);
+        prev = Some(burrow);
+    }
+}
+
+
+fn solve(lines: &[String], options: &[String]) {
+    let burrow = Burrow::parse(lines).expect("Couldn't parse the burrow");
+    println!("{}", &burrow);
+    match least_energy(&burrow) {
+        Some((energy, path)) => {
+            if utils::has_option(options, "--trace") {
+                print_trace(&path);
+            }
+            println!("Least energy to organise the amphipods: {}", energy);
+        },
+        None => println!("The amphipods can't be organised!"),
+    }
+}
+
+
+pub fn day23_1(options: &[String]) {
+    println!("Day 23: Amphipod, part 1");
+    let file_name = utils::input_file(options, "./input/day23.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Couldn't read file");
+    solve(&lines, options);
+}
+
+
+pub fn day23_2(options: &[String]) {
+    println!("Day 23: Amphipod, part 2");
+    let file_name = utils::input_file(options, "./input/day23.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Couldn't read file");
+    solve(&unfold(&lines), options);
+}
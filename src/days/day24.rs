@@ -0,0 +1,372 @@
+//--- Day 24: Arithmetic Logic Unit ---
+
+//Magic smoke starts leaking from the submarine's arithmetic logic unit (ALU). Without the ability
+//to perform basic arithmetic and logic functions, the submarine can't produce cool patterns with
+//its Christmas lights!
+
+//It also can't navigate. Or run the oxygen system.
+
+//Don't worry, though - you probably have enough oxygen left to give you enough time to build a new
+//ALU.
+
+use std::str::FromStr;
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::utils;
+
+
+#[derive(Error, Debug, Clone)]
+pub enum DecodeError {
+    #[error("Invalid instruction: {0}")]
+    InvalidError(String),
+}
+
+
+#[derive(Error, Debug, Clone)]
+pub enum AluError {
+    #[error("Ran out of input at instruction {0}")]
+    NoInput(usize),
+    #[error("Division by zero at instruction {0}")]
+    DivideByZero(usize),
+    #[error("Invalid modulo at instruction {0}")]
+    InvalidMod(usize),
+    #[error("Overflow at instruction {0}")]
+    Overflow(usize),
+    #[error("A model number has {MODEL_DIGITS} digits, not {0}")]
+    WrongLength(usize),
+}
+
+
+const MODEL_DIGITS: usize = 14;
+
+
+const NUM_REGS: usize = 4;
+const Z: usize = 3;
+
+type Registers = [i64; NUM_REGS];
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operand {
+    Reg(usize),
+    Value(i64),
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instruction {
+    Inp(usize),
+    Add(usize, Operand),
+    Mul(usize, Operand),
+    Div(usize, Operand),
+    Mod(usize, Operand),
+    Eql(usize, Operand),
+}
+
+use Instruction::*;
+
+
+fn parse_register(s: &str) -> Result<usize, DecodeError> {
+    match s {
+        "w" => Ok(0),
+        "x" => Ok(1),
+        "y" => Ok(2),
+        "z" => Ok(3),
+        _ => Err(DecodeError::InvalidError(format!("'{}' isn't a register", s))),
+    }
+}
+
+
+impl FromStr for Operand {
+    type Err = DecodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(r) = parse_register(s) {
+            return Ok(Operand::Reg(r));
+        }
+        s.parse::<i64>()
+            .map(Operand::Value)
+            .map_err(|e| DecodeError::InvalidError(format!("'{}' isn't a register or number: {}", s, e)))
+    }
+}
+
+
+impl FromStr for Instruction {
+    type Err = DecodeError;
+
+    // parse "inp w", "add x 1", "mul y z", etc.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        match parts[..] {
+            ["inp", a] => Ok(Inp(parse_register(a)?)),
+            [op, a, b] => {
+                let a = parse_register(a)?;
+                let b = b.parse::<Operand>()?;
+                match op {
+                    "add" => Ok(Add(a, b)),
+                    "mul" => Ok(Mul(a, b)),
+                    "div" => Ok(Div(a, b)),
+                    "mod" => Ok(Mod(a, b)),
+                    "eql" => Ok(Eql(a, b)),
+                    _ => Err(DecodeError::InvalidError(format!("Unknown instruction: {}", line))),
+                }
+            },
+            _ => Err(DecodeError::InvalidError(format!("Can't decode: {}", line))),
+        }
+    }
+}
+
+
+impl Instruction {
+
+    // the registers this instruction reads and the one it writes.  'mul r 0' just zeros 'r' so it
+    // doesn't really read it, which matters as MONAD uses it to clear registers.
+    fn reads_writes(&self) -> (Vec<usize>, usize) {
+        match *self {
+            Inp(a) => (vec![], a),
+            Mul(a, Operand::Value(0)) => (vec![], a),
+            Add(a, b) | Mul(a, b) | Div(a, b) | Mod(a, b) | Eql(a, b) => match b {
+                Operand::Reg(r) => (vec![a, r], a),
+                Operand::Value(_) => (vec![a], a),
+            },
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, Default)]
+struct Alu {
+    regs: Registers,
+}
+
+
+impl Alu {
+
+    fn value(&self, operand: Operand) -> i64 {
+        match operand {
+            Operand::Reg(r) => self.regs[r],
+            Operand::Value(v) => v,
+        }
+    }
+
+    // run the program against the inputs; errors carry the index of the failing instruction.
+    fn run<I>(&mut self, program: &[Instruction], inputs: &mut I) -> Result<(), AluError>
+        where I: Iterator<Item=i64>
+    {
+        for (i, instruction) in program.iter().enumerate() {
+            match *instruction {
+                Inp(a) => { self.regs[a] = inputs.next().ok_or(AluError::NoInput(i))? },
+                Add(a, b) => {
+                    self.regs[a] = self.regs[a].checked_add(self.value(b)).ok_or(AluError::Overflow(i))?;
+                },
+                Mul(a, b) => {
+                    self.regs[a] = self.regs[a].checked_mul(self.value(b)).ok_or(AluError::Overflow(i))?;
+                },
+                Div(a, b) => {
+                    let v = self.value(b);
+                    if v == 0 {
+                        return Err(AluError::DivideByZero(i));
+                    }
+                    self.regs[a] = self.regs[a].checked_div(v).ok_or(AluError::Overflow(i))?;
+                },
+                Mod(a, b) => {
+                    let v = self.value(b);
+                    if self.regs[a] < 0 || v <= 0 {
+                        return Err(AluError::InvalidMod(i));
+                    }
+                    self.regs[a] %= v;
+                },
+                Eql(a, b) => { self.regs[a] = (self.regs[a] == self.value(b)) as i64 },
+            }
+        }
+        Ok(())
+    }
+}
+
+
+// MONAD's blocks are all the same apart from three numbers: z is divided by 'div', and then
+// unless the digit is z % 26 + 'check' it's multiplied by 26 and digit + 'offset' is added.  With
+// offset >= -1 a block never leaves z below z / div, so z can only get back to 0 if it's less than
+// the product of the divisors left.  Returns div if the block is one of these.
+fn monad_divisor(block: &[Instruction]) -> Option<i64> {
+    let (w, x, y) = (0, 1, 2);
+    let div = match block.get(4) {
+        Some(&Div(Z, Operand::Value(div))) if div > 0 => div,
+        _ => return None,
+    };
+    let check = match block.get(5) {
+        Some(&Add(1, Operand::Value(check))) => check,
+        _ => return None,
+    };
+    let offset = match block.get(15) {
+        Some(&Add(2, Operand::Value(offset))) if offset >= -1 => offset,
+        _ => return None,
+    };
+    let monad = [
+        Inp(w), Mul(x, Operand::Value(0)), Add(x, Operand::Reg(Z)), Mod(x, Operand::Value(26)),
+        Div(Z, Operand::Value(div)), Add(x, Operand::Value(check)), Eql(x, Operand::Reg(w)),
+        Eql(x, Operand::Value(0)), Mul(y, Operand::Value(0)), Add(y, Operand::Value(25)),
+        Mul(y, Operand::Reg(x)), Add(y, Operand::Value(1)), Mul(Z, Operand::Reg(y)),
+        Mul(y, Operand::Value(0)), Add(y, Operand::Reg(w)), Add(y, Operand::Value(offset)),
+        Mul(y, Operand::Reg(x)), Add(Z, Operand::Reg(y)),
+    ];
+    (block == monad).then_some(div)
+}
+
+
+// The program split up at each 'inp' so that a block handles one digit.  'live' is, for each
+// block, which registers it (or a later block) needs from the previous ones; these are the only
+// ones that need to go into the memo key.  For MONAD that turns out to be just z.  If every block
+// is a MONAD block then 'limits' has, for each block, the product of the divisors from there on.
+#[derive(Clone, Debug)]
+struct Blocks {
+    prelude: Vec<Instruction>,
+    blocks: Vec<Vec<Instruction>>,
+    live: Vec<[bool; NUM_REGS]>,
+    limits: Option<Vec<i64>>,
+}
+
+
+impl Blocks {
+
+    fn new(program: &[Instruction]) -> Self {
+        let mut prelude: Vec<Instruction> = Vec::new();
+        let mut blocks: Vec<Vec<Instruction>> = Vec::new();
+        for instruction in program.iter() {
+            match (instruction, blocks.last_mut()) {
+                (Inp(_), _) => blocks.push(vec![*instruction]),
+                (_, Some(block)) => block.push(*instruction),
+                (_, None) => prelude.push(*instruction),
+            }
+        }
+        // work backwards; only z matters at the end of the program.
+        let mut live: Vec<[bool; NUM_REGS]> = vec![[false; NUM_REGS]; blocks.len()];
+        let mut live_out = [false; NUM_REGS];
+        live_out[Z] = true;
+        for (b, block) in blocks.iter().enumerate().rev() {
+            let mut live_in = live_out;
+            for instruction in block.iter().rev() {
+                let (reads, write) = instruction.reads_writes();
+                live_in[write] = false;
+                for r in reads {
+                    live_in[r] = true;
+                }
+            }
+            live[b] = live_in;
+            live_out = live_in;
+        }
+        let limits = blocks.iter()
+            .map(|block| monad_divisor(block))
+            .collect::<Option<Vec<_>>>()
+            .map(|divisors| {
+                let mut limits = divisors.iter()
+                    .rev()
+                    .scan(1i64, |product, &d| { *product = product.saturating_mul(d); Some(*product) })
+                    .collect::<Vec<_>>();
+                limits.reverse();
+                limits
+            });
+        Self {prelude, blocks, live, limits}
+    }
+
+    fn key(&self, block: usize, regs: &Registers) -> Registers {
+        let mut key = [0; NUM_REGS];
+        for r in 0..NUM_REGS {
+            if self.live[block][r] {
+                key[r] = regs[r];
+            }
+        }
+        key
+    }
+}
+
+
+// depth first search across the blocks trying the digits in the order given.  The first number
+// found is then the best for that order.  Dead ends are remembered for each block by its live
+// registers (for MONAD, just z) so that they are never explored twice, and a z too big for the
+// blocks left to bring back to 0 is a dead end straight away.
+fn search(blocks: &Blocks, block: usize, regs: Registers, digits: &[i64],
+          dead: &mut [HashSet<Registers>]) -> Option<Vec<i64>>
+{
+    if block == blocks.blocks.len() {
+        return if regs[Z] == 0 { Some(vec![]) } else { None };
+    }
+    if blocks.limits.as_ref().is_some_and(|limits| regs[Z] >= limits[block]) {
+        return None;
+    }
+    let key = blocks.key(block, &regs);
+    if dead[block].contains(&key) {
+        return None;
+    }
+    for &digit in digits.iter() {
+        let mut alu = Alu { regs };
+        if alu.run(&blocks.blocks[block], &mut std::iter::once(digit)).is_err() {
+            continue;
+        }
+        if let Some(mut rest) = search(blocks, block + 1, alu.regs, digits, dead) {
+            rest.insert(0, digit);
+            return Some(rest);
+        }
+    }
+    dead[block].insert(key);
+    None
+}
+
+
+fn find_model_number(program: &[Instruction], largest: bool) -> Result<Option<Vec<i64>>, AluError> {
+    let blocks = Blocks::new(program);
+    if blocks.blocks.len() != MODEL_DIGITS {
+        return Err(AluError::WrongLength(blocks.blocks.len()));
+    }
+    let mut alu = Alu::default();
+    alu.run(&blocks.prelude, &mut std::iter::empty())?;
+    let digits: Vec<i64> = if largest { (1..=9).rev().collect() } else { (1..=9).collect() };
+    let mut dead: Vec<HashSet<Registers>> = vec![HashSet::new(); blocks.blocks.len()];
+    Ok(search(&blocks, 0, alu.regs, &digits, &mut dead))
+}
+
+
+// run the whole program on the model number to check that it really is valid.
+fn check_model_number(program: &[Instruction], digits: &[i64]) -> Result<bool, AluError> {
+    if digits.len() != MODEL_DIGITS {
+        return Err(AluError::WrongLength(digits.len()));
+    }
+    let mut alu = Alu::default();
+    alu.run(program, &mut digits.iter().cloned())?;
+    Ok(alu.regs[Z] == 0)
+}
+
+
+fn solve(options: &[String], largest: bool) {
+    let file_name = utils::input_file(options, "./input/day24.txt");
+    let program = utils::read_file_single_result::<Instruction>(&file_name)
+        .expect("Couldn't read file");
+    println!("Program has {} instructions.", program.len());
+    match find_model_number(&program, largest) {
+        Ok(Some(digits)) => {
+            let number = digits.iter().map(|d| d.to_string()).collect::<String>();
+            println!("{} model number is {}", if largest { "Largest" } else { "Smallest" }, number);
+            match check_model_number(&program, &digits) {
+                Ok(true) => println!("Checked: the full program accepts {}", number),
+                Ok(false) => println!("Check failed: the full program rejects {}!", number),
+                Err(e) => println!("Check failed: the ALU errored on {}: {}", number, e),
+            }
+        },
+        Ok(None) => println!("No model number is valid!"),
+        Err(e) => println!("Can't search for a model number: {}", e),
+    }
+}
+
+
+pub fn day24_1(options: &[String]) {
+    println!("Day 24: Arithmetic Logic Unit, part 1");
+    solve(options, true);
+}
+
+
+pub fn day24_2(options: &[String]) {
+    println!("Day 24: Arithmetic Logic Unit, part 2");
+    solve(options, false);
+}
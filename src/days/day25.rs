@@ -0,0 +1,87 @@
+//--- Day 25: Sea Cucumber ---
+
+//This is it: the bottom of the ocean trench, the last place the sleigh keys could be. Your
+//submarine's experimental antenna still isn't boosted enough to detect the keys, but they must be
+//here. All you need to do is reach the seafloor and find them.
+
+//At least, you'd touch down on the seafloor if you could; unfortunately, it's completely covered
+//by two large herds of sea cucumbers, and there isn't an open space large enough for your
+//submarine.
+
+use crate::automaton::{Automaton, Boundary, Grid, Neighbourhood, Rule};
+use crate::utils;
+
+
+const EMPTY: char = '.';
+const EAST: char = '>';
+const SOUTH: char = 'v';
+
+
+// The east herd moves in the first phase of a step and the south herd in the second; a cell
+// only looks at the cell behind it (to see if something moves in) and in front of it (to see
+// if it can move out).  The sea floor wraps round at the edges.
+struct SeaCucumbers;
+
+
+impl Rule for SeaCucumbers {
+    type Cell = char;
+
+    fn phases(&self) -> Vec<Neighbourhood> {
+        vec![
+            Neighbourhood::Offsets(vec![(0, -1), (0, 1)]),
+            Neighbourhood::Offsets(vec![(-1, 0), (1, 0)]),
+        ]
+    }
+
+    fn next(&self, phase: usize, cell: &char, neighbours: &[Option<&char>]) -> char {
+        let herd = if phase == 0 { EAST } else { SOUTH };
+        match (*cell, neighbours) {
+            (EMPTY, [Some(&behind), _]) if behind == herd => herd,
+            (c, [_, Some(&EMPTY)]) if c == herd => EMPTY,
+            (c, _) => c,
+        }
+    }
+}
+
+
+fn parse<S>(lines: &[S]) -> Result<Grid<char>, String>
+    where S: AsRef<str>
+{
+    let rows = lines.iter()
+        .map(|l| l.as_ref().trim())
+        .filter(|l| !l.is_empty())
+        .map(|line| line.chars()
+             .map(|c| match c {
+                 EMPTY | EAST | SOUTH => Ok(c),
+                 _ => Err(format!("'{}' isn't a sea cucumber or empty: {}", c, line)),
+             })
+             .collect::<Result<Vec<_>, _>>())
+        .collect::<Result<Vec<_>, _>>()?;
+    Grid::new(rows, EMPTY)
+}
+
+
+// the first step on which nothing moves, and the sea floor then.
+fn steps_until_stopped(floor: &Grid<char>) -> (usize, Grid<char>) {
+    let mut herds = Automaton::new(SeaCucumbers, floor.clone(), Boundary::Toroidal)
+        .expect("Couldn't make the automaton");
+    let steps = herds.run_until(None, |_, stats| stats.changed == 0).unwrap();
+    (steps, herds.grid)
+}
+
+
+pub fn day25_1(options: &[String]) {
+    println!("Day 25: Sea Cucumber, part 1");
+    let file_name = utils::input_file(options, "./input/day25.txt");
+    let lines = utils::read_file_single_result::<String>(&file_name).expect("Couldn't read file");
+    let floor = parse(&lines).expect("Couldn't parse the sea floor");
+    let (steps, stopped) = steps_until_stopped(&floor);
+    println!("Stopped:\n{}", stopped);
+    println!("First step where no sea cucumbers move: {}", steps);
+}
+
+
+pub fn day25_2() {
+    println!("Day 25: Sea Cucumber, part 2");
+    println!("There's no puzzle for part 2; it needs all the other stars to start the sleigh.");
+}
@@ -16,3 +16,6 @@ pub mod day15;
 pub mod day16;
 pub mod day17;
 pub mod day18;
+pub mod day23;
+pub mod day24;
+pub mod day25;
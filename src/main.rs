@@ -7,10 +7,12 @@ use std::process;
 
 mod days;
 mod utils;
+mod automaton;
 
 struct Config {
     day: u32,
     part: u32,
+    options: Vec<String>,  // anything after the day-part, e.g. --trace
 }
 
 
@@ -21,54 +23,55 @@ impl Config {
     fn new(args: &[String]) -> Result<Config, String> {
         let num_args = args.len();
         if num_args == 1 {
-            return Ok(Config {day: 1, part: 1})
+            return Ok(Config {day: 1, part: 1, options: vec![]})
         }
         let command = args[1].to_lowercase();
-        let parts = command.split("-").into_iter().collect::<Vec<&str>>();
+        let parts = command.split('-').collect::<Vec<&str>>();
         if parts.len() != 2 {
             return Err(format!("command '{}' isn't a valid day-part", command));
         }
         let day: u32 = parts[0].parse().unwrap_or(0);
         let part: u32 = parts[1].parse().unwrap_or(0);
-        if day < 1 || day > MAX_DAY || part < 1 || part > 2 {
+        if !(1..=MAX_DAY).contains(&day) || !(1..=2).contains(&part) {
             return Err(format!("day or part is not parsable as an int or not in range: input was '{}'", command));
         }
-        return Ok(Config{day, part});
+        let options = args[2..].to_vec();
+        Ok(Config{day, part, options})
     }
 }
 
 
 
 fn usage() -> Result<(), String> {
-    eprintln!("Usage: aoc2021 <day>-<part>");
+    eprintln!("Usage: aoc2021 <day>-<part> [options]");
     Ok(())
 }
 
 
-fn run_day_part(day: u32, part: u32) {
+fn run_day_part(day: u32, part: u32, options: &[String]) {
     match (day, part) {
-        (1,1) => days::day01::day1_1(),
-        (1,2) => days::day01::day1_2(),
-        (2,1) => days::day02::day2_1(),
-        (2,2) => days::day02::day2_2(),
-        (3,1) => days::day03::day3_1(),
-        (3,2) => days::day03::day3_2(),
-        (4,1) => days::day04::day4_1(),
-        (4,2) => days::day04::day4_2(),
-        (5,1) => days::day05::day5_1(),
-        (5,2) => days::day05::day5_2(),
-        (6,1) => days::day06::day6_1(),
-        (6,2) => days::day06::day6_2(),
-        (7,1) => days::day07::day7_1(),
-        (7,2) => days::day07::day7_2(),
-        (8,1) => days::day08::day8_1(),
-        (8,2) => days::day08::day8_2(),
-        (9,1) => days::day09::day9_1(),
-        (9,2) => days::day09::day9_2(),
-        (10,1) => days::day10::day10_1(),
-        (10,2) => days::day10::day10_2(),
-        (11,1) => days::day11::day11_1(),
-        (11,2) => days::day11::day11_2(),
+        (1,1) => days::day01::day1_1(options),
+        (1,2) => days::day01::day1_2(options),
+        (2,1) => days::day02::day2_1(options),
+        (2,2) => days::day02::day2_2(options),
+        (3,1) => days::day03::day3_1(options),
+        (3,2) => days::day03::day3_2(options),
+        (4,1) => days::day04::day4_1(options),
+        (4,2) => days::day04::day4_2(options),
+        (5,1) => days::day05::day5_1(options),
+        (5,2) => days::day05::day5_2(options),
+        (6,1) => days::day06::day6_1(options),
+        (6,2) => days::day06::day6_2(options),
+        (7,1) => days::day07::day7_1(options),
+        (7,2) => days::day07::day7_2(options),
+        (8,1) => days::day08::day8_1(options),
+        (8,2) => days::day08::day8_2(options),
+        (9,1) => days::day09::day9_1(options),
+        (9,2) => days::day09::day9_2(options),
+        (10,1) => days::day10::day10_1(options),
+        (10,2) => days::day10::day10_2(options),
+        (11,1) => days::day11::day11_1(options),
+        (11,2) => days::day11::day11_2(options),
         (12,1) => days::day12::day12_1(),
         (12,2) => days::day12::day12_2(),
         (13,1) => days::day13::day13_1(),
@@ -83,6 +86,12 @@ fn run_day_part(day: u32, part: u32) {
         (17,2) => days::day17::day17_2(),
         (18,1) => days::day18::day18_1(),
         (18,2) => days::day18::day18_2(),
+        (23,1) => days::day23::day23_1(options),
+        (23,2) => days::day23::day23_2(options),
+        (24,1) => days::day24::day24_1(options),
+        (24,2) => days::day24::day24_2(options),
+        (25,1) => days::day25::day25_1(options),
+        (25,2) => days::day25::day25_2(),
         _ => println!("Day {0}-{1} not defined (yet?)", day, part),
     }
 }
@@ -96,5 +105,5 @@ fn main() {
         process::exit(1);
     });
     println!("the day is {}-{}", config.day, config.part);
-    run_day_part(config.day, config.part);
+    run_day_part(config.day, config.part, &config.options);
 }
@@ -2,6 +2,12 @@
 
 
 use std::str::FromStr;
+use std::fmt;
+use std::hash::Hash;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use priority_queue::PriorityQueue;
 
 
 /// Read lines from a file and parse them into a vector; blows up if the file is not found.
@@ -56,14 +62,107 @@ pub fn read_file_single_result<T>(file_name: &str) -> Result<Vec<T>, <T as FromS
 /// count the number of bits in a
 pub fn count_bits(v: usize, n: usize) -> usize
 {
-    let mut count: usize = 0;
-    let mut bit: usize = 1;
-    for _ in 0..n {
-        if v & bit == bit {
-            count +=1;
+    let mask = if n >= usize::BITS as usize { usize::MAX } else { (1 << n) - 1 };
+    (v & mask).count_ones() as usize
+}
+
+
+/// count the number of set bits in each of the first 'width' bit positions across all the words.
+/// Index 0 of the result is the least significant bit.
+pub fn count_column_bits(words: &[u128], width: usize) -> Vec<usize>
+{
+    let mut counts: Vec<usize> = vec![0; width];
+    for &word in words.iter() {
+        let mut w = word;
+        while w != 0 {
+            let bit = w.trailing_zeros() as usize;
+            if bit >= width {
+                break;
+            }
+            counts[bit] += 1;
+            w &= w - 1;
         }
-        bit *= 2;
     }
-    count
+    counts
+}
+
+
+/// See if a flag (e.g. "--trace") was passed in the options after the day-part.
+pub fn has_option(options: &[String], flag: &str) -> bool {
+    options.iter().any(|o| o == flag)
+}
+
+
+/// Find the value that follows an option (e.g. "--window 3") and parse it as a T.  Returns
+/// Ok(None) if the option wasn't passed at all, and an Err if it has no value or won't parse.
+pub fn option_value<T>(options: &[String], flag: &str) -> Result<Option<T>, String>
+    where T: FromStr,
+          <T as FromStr>::Err: fmt::Display,
+{
+    match options.iter().position(|o| o == flag) {
+        None => Ok(None),
+        Some(i) => {
+            let value = options.get(i + 1)
+                .ok_or_else(|| format!("Option {} needs a value", flag))?;
+            value.parse::<T>()
+                .map(Some)
+                .map_err(|e| format!("Option {} value '{}' isn't valid: {}", flag, value, e))
+        },
+    }
+}
+
+
+/// The input file to use for a day; "--input <file>" overrides the default.
+pub fn input_file(options: &[String], default: &str) -> String {
+    match option_value::<String>(options, "--input") {
+        Ok(Some(file_name)) => file_name,
+        _ => default.to_string(),
+    }
 }
 
+
+/// Least cost search from `start` until `is_goal` says we've arrived.  `neighbours` returns the
+/// states reachable in one move along with the cost of that move.  `heuristic` must never
+/// over-estimate the remaining cost; returning 0 gives plain Dijkstra, anything else is A*.
+/// Returns the total cost and the states visited from start to goal (inclusive), or None if the
+/// goal can't be reached.
+pub fn least_cost_path<S, N, I, G, H>(start: S, mut neighbours: N, mut is_goal: G, mut heuristic: H)
+    -> Option<(usize, Vec<S>)>
+    where S: Clone + Eq + Hash,
+          N: FnMut(&S) -> I,
+          I: IntoIterator<Item=(S, usize)>,
+          G: FnMut(&S) -> bool,
+          H: FnMut(&S) -> usize,
+{
+    let mut pq: PriorityQueue<S, Reverse<usize>> = PriorityQueue::new();
+    let mut costs: HashMap<S, usize> = HashMap::new();
+    let mut came_from: HashMap<S, S> = HashMap::new();
+    let priority = heuristic(&start);
+    costs.insert(start.clone(), 0);
+    pq.push(start, Reverse(priority));
+
+    while let Some((state, _)) = pq.pop() {
+        let cost = costs[&state];
+        if is_goal(&state) {
+            let mut path = vec![state];
+            while let Some(prev) = came_from.get(path.last().unwrap()) {
+                path.push(prev.clone());
+            }
+            path.reverse();
+            return Some((cost, path));
+        }
+        for (next, step_cost) in neighbours(&state) {
+            let next_cost = cost + step_cost;
+            if let Some(&prev_cost) = costs.get(&next) {
+                if prev_cost <= next_cost {
+                    continue;
+                }
+            }
+            costs.insert(next.clone(), next_cost);
+            came_from.insert(next.clone(), state.clone());
+            let priority = next_cost + heuristic(&next);
+            pq.push(next, Reverse(priority));
+        }
+    }
+    None
+}